@@ -0,0 +1,371 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::math_utils::FloatType;
+use crate::math_utils::quaternion::Quaternion;
+use crate::math_utils::vector::Vector3;
+use crate::transform::Transform;
+
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AnimationError {
+    #[error("Animation track needs at least one keyframe")]
+    NoKeyframes,
+
+    #[error("{0:?} interpolation is not supported by this kind of track")]
+    UnsupportedInterpolation(Interpolation)
+}
+
+// How values are blended between two keyframes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    // Jumps to the next value once its keyframe is reached
+    Step,
+    #[default]
+    Linear,
+    // Catmull-Rom spline through the keyframes, only for vectors
+    Cubic,
+    // Constant angular speed along the shortest arc, only for rotations
+    Slerp
+}
+
+// What happens once the time goes past the end of an animation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    // Hold the last value
+    #[default]
+    Once,
+    // Start over from the beginning
+    Repeat,
+    // Play backwards to the beginning, then forwards again
+    PingPong
+}
+
+impl LoopMode {
+    // Maps any time into [0, duration]
+    pub fn wrap_time(&self, time: FloatType, duration: FloatType) -> FloatType {
+        if duration <= 0.0 {
+            return 0.0;
+        }
+
+        match self {
+            LoopMode::Once => time.clamp(0.0, duration),
+            LoopMode::Repeat => time.rem_euclid(duration),
+            LoopMode::PingPong => {
+                let time = time.rem_euclid(2.0 * duration);
+                if time > duration { 2.0 * duration - time } else { time }
+            }
+        }
+    }
+}
+
+// Values that a track can animate
+pub trait Animatable: Copy {
+    fn supports(interpolation: Interpolation) -> bool;
+
+    // Blends between points[1] and points[2] at `u` in [0, 1]. The outer points and all the
+    // times are only used by cubic interpolation. Never called with `Interpolation::Step`.
+    fn interpolate(
+        points: [Self; 4],
+        times: [FloatType; 4],
+        u: FloatType,
+        interpolation: Interpolation
+    ) -> Self;
+}
+
+impl Animatable for Vector3<FloatType> {
+    fn supports(interpolation: Interpolation) -> bool {
+        interpolation != Interpolation::Slerp
+    }
+
+    fn interpolate(
+        points: [Self; 4],
+        times: [FloatType; 4],
+        u: FloatType,
+        interpolation: Interpolation
+    ) -> Self {
+        if interpolation == Interpolation::Cubic {
+            return catmull_rom(points, times, u);
+        }
+
+        let [_, start, end, _] = points;
+        start.lerp(end, u)
+    }
+}
+
+impl Animatable for Quaternion {
+    fn supports(interpolation: Interpolation) -> bool {
+        interpolation != Interpolation::Cubic
+    }
+
+    fn interpolate(
+        points: [Self; 4],
+        _times: [FloatType; 4],
+        u: FloatType,
+        interpolation: Interpolation
+    ) -> Self {
+        let [_, start, end, _] = points;
+        if interpolation == Interpolation::Slerp {
+            return start.slerp(end, u);
+        }
+
+        start.nlerp(end, u)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<T> {
+    // Seconds since the start of the animation
+    pub time: FloatType,
+    pub value: T
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: FloatType, value: T) -> Self {
+        Self { time, value }
+    }
+}
+
+// Keyframes of a single property. Before the first and after the last keyframe, the value
+// stays at that keyframe's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    // Sorted by time
+    keyframes: Vec<Keyframe<T>>,
+    interpolation: Interpolation
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(mut keyframes: Vec<Keyframe<T>>, interpolation: Interpolation) -> Result<Self, AnimationError> {
+        if keyframes.is_empty() {
+            return Err(AnimationError::NoKeyframes);
+        }
+        if !T::supports(interpolation) {
+            return Err(AnimationError::UnsupportedInterpolation(interpolation));
+        }
+
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(Self { keyframes, interpolation })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn end_time(&self) -> FloatType {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    pub fn sample(&self, time: FloatType) -> T {
+        let last = self.keyframes.len() - 1;
+        let end = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if end == 0 {
+            return self.keyframes[0].value;
+        }
+        if end > last {
+            return self.keyframes[last].value;
+        }
+
+        let start = end - 1;
+        let (t1, t2) = (self.keyframes[start].time, self.keyframes[end].time);
+        let u = if t2 > t1 { (time - t1) / (t2 - t1) } else { 1.0 };
+        if self.interpolation == Interpolation::Step {
+            return self.keyframes[start].value;
+        }
+
+        // Missing neighbours at the ends repeat the end keyframe
+        let before = &self.keyframes[start.saturating_sub(1)];
+        let after = &self.keyframes[(end + 1).min(last)];
+
+        T::interpolate(
+            [before.value, self.keyframes[start].value, self.keyframes[end].value, after.value],
+            [before.time, t1, t2, after.time],
+            u,
+            self.interpolation
+        )
+    }
+}
+
+// Animates the transform of one object. Properties without a track are left alone.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TransformAnimation {
+    pub position: Option<Track<Vector3<FloatType>>>,
+    pub rotation: Option<Track<Quaternion>>,
+    pub scale: Option<Track<Vector3<FloatType>>>,
+    pub loop_mode: LoopMode
+}
+
+impl TransformAnimation {
+    // The animation starts at time 0 and ends on the last keyframe of its longest track
+    pub fn duration(&self) -> FloatType {
+        [
+            self.position.as_ref().map(Track::end_time),
+            self.rotation.as_ref().map(Track::end_time),
+            self.scale.as_ref().map(Track::end_time)
+        ].into_iter().flatten().fold(0.0, FloatType::max)
+    }
+
+    // `time` is in seconds since the animation started. The transform still has to be updated
+    // afterwards.
+    pub fn apply(&self, transform: &mut Transform, time: FloatType) {
+        let time = self.loop_mode.wrap_time(time, self.duration());
+
+        if let Some(track) = &self.position {
+            transform.set_position(track.sample(time));
+        }
+        if let Some(track) = &self.rotation {
+            transform.set_rotation(track.sample(time));
+        }
+        if let Some(track) = &self.scale {
+            transform.set_scale(track.sample(time));
+        }
+    }
+}
+
+// Evaluates the segment between points[1] and points[2] at `u` in [0, 1].
+// Tangents are scaled by the keyframe times, so unevenly spaced keyframes do not make the value
+// overshoot or speed up. See:
+// https://en.wikipedia.org/wiki/Cubic_Hermite_spline#Catmull%E2%80%93Rom_spline
+pub(crate) fn catmull_rom(
+    points: [Vector3<FloatType>; 4],
+    times: [FloatType; 4],
+    u: FloatType
+) -> Vector3<FloatType> {
+    let [p0, p1, p2, p3] = points;
+    let [t0, t1, t2, t3] = times;
+    let segment_duration = t2 - t1;
+
+    let tangent = |before: Vector3<FloatType>, after: Vector3<FloatType>, span: FloatType| {
+        if span > 0.0 {
+            (after - before) * (segment_duration / span)
+        }
+        else {
+            Vector3::default()
+        }
+    };
+    let m1 = tangent(p0, p2, t2 - t0);
+    let m2 = tangent(p1, p3, t3 - t1);
+
+    // Hermite basis functions
+    let u2 = u * u;
+    let u3 = u2 * u;
+    let h00 = 2.0 * u3 - 3.0 * u2 + 1.0;
+    let h10 = u3 - 2.0 * u2 + u;
+    let h01 = -2.0 * u3 + 3.0 * u2;
+    let h11 = u3 - u2;
+
+    p1 * h00 + m1 * h10 + p2 * h01 + m2 * h11
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use float_cmp::ApproxEq;
+    use crate::math_utils::vector::{vector, Vector};
+    use crate::math_utils::vector::tests::approx_cmp_vector;
+    use crate::math_utils::quaternion::tests::approx_cmp_quaternion;
+    use crate::math_utils::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn init_test_track(interpolation: Interpolation) -> Track<Vector3<FloatType>> {
+        Track::new(vec![
+            Keyframe::new(2.0, vector![2.0, 4.0, 0.0]),
+            Keyframe::new(0.0, vector![0.0, 0.0, 0.0]),
+            Keyframe::new(1.0, vector![1.0, 0.0, 0.0])
+        ], interpolation).unwrap()
+    }
+
+    #[test]
+    fn test_track_validation() {
+        assert_eq!(
+            Track::<Vector3<FloatType>>::new(vec![], Interpolation::Linear),
+            Err(AnimationError::NoKeyframes)
+        );
+        assert_eq!(
+            Track::new(vec![Keyframe::new(0.0, vector![0.0, 0.0, 0.0])], Interpolation::Slerp),
+            Err(AnimationError::UnsupportedInterpolation(Interpolation::Slerp))
+        );
+        assert_eq!(
+            Track::new(vec![Keyframe::new(0.0, Quaternion::default())], Interpolation::Cubic),
+            Err(AnimationError::UnsupportedInterpolation(Interpolation::Cubic))
+        );
+    }
+
+    #[test]
+    fn test_step_track() {
+        let track = init_test_track(Interpolation::Step);
+
+        approx_cmp_vector(track.sample(0.9), vector![0.0, 0.0, 0.0]);
+        approx_cmp_vector(track.sample(1.0), vector![1.0, 0.0, 0.0]);
+        approx_cmp_vector(track.sample(5.0), vector![2.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_linear_track() {
+        let track = init_test_track(Interpolation::Linear);
+
+        approx_cmp_vector(track.sample(-1.0), vector![0.0, 0.0, 0.0]);
+        approx_cmp_vector(track.sample(0.5), vector![0.5, 0.0, 0.0]);
+        approx_cmp_vector(track.sample(1.5), vector![1.5, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_cubic_track() {
+        let track = init_test_track(Interpolation::Cubic);
+
+        // Passes through the keyframes, but curves between them: y starts off flat after the
+        // first segment, so it lags behind the straight line (which would give 2)
+        approx_cmp_vector(track.sample(1.0), vector![1.0, 0.0, 0.0]);
+        assert!(track.sample(1.5).approx_eq(vector![1.5, 1.75, 0.0], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_rotation_track() {
+        let quarter_turn = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+        let keyframes = vec![Keyframe::new(0.0, Quaternion::default()), Keyframe::new(1.0, quarter_turn)];
+        let expected = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_4);
+
+        let slerp = Track::new(keyframes.clone(), Interpolation::Slerp).unwrap();
+        assert!(slerp.sample(0.5).approx_eq(expected, (1e-6, 4)));
+
+        // Halfway is the same for both, only the speed differs elsewhere
+        let linear = Track::new(keyframes, Interpolation::Linear).unwrap();
+        assert!(linear.sample(0.5).approx_eq(expected, (1e-6, 4)));
+        assert!(!linear.sample(0.25).approx_eq(slerp.sample(0.25), (1e-6, 4)));
+    }
+
+    #[test]
+    fn test_loop_modes() {
+        assert_eq!(LoopMode::Once.wrap_time(5.0, 2.0), 2.0);
+        assert_eq!(LoopMode::Repeat.wrap_time(5.0, 2.0), 1.0);
+        assert_eq!(LoopMode::Repeat.wrap_time(-0.5, 2.0), 1.5);
+        assert_eq!(LoopMode::PingPong.wrap_time(3.5, 2.0), 0.5);
+        assert_eq!(LoopMode::PingPong.wrap_time(4.5, 2.0), 0.5);
+    }
+
+    #[test]
+    fn test_apply_animation() {
+        let animation = TransformAnimation {
+            position: Some(init_test_track(Interpolation::Linear)),
+            scale: Some(Track::new(vec![Keyframe::new(4.0, vector![2.0, 2.0, 2.0])], Interpolation::Step).unwrap()),
+            loop_mode: LoopMode::Repeat,
+            ..Default::default()
+        };
+        assert_eq!(animation.duration(), 4.0);
+
+        let mut transform = Transform::default();
+        animation.apply(&mut transform, 4.5);
+        transform.update();
+
+        approx_cmp_vector(transform.position(), vector![0.5, 0.0, 0.0]);
+        approx_cmp_vector(transform.scale(), vector![2.0, 2.0, 2.0]);
+        approx_cmp_quaternion(transform.rotation(), Quaternion::default());
+    }
+}
@@ -1,42 +1,155 @@
-use std::f32::consts::PI;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
 
-use crate::math_utils::vector::Vector3;
+use crate::math_utils::vector::{Vector2, Vector3, Vector4};
+use crate::math_utils::matrix::{Matrix3, Matrix4};
+use crate::math_utils::quaternion::Quaternion;
+use crate::math_utils::geometry::{Frustum, Plane, Ray, Sphere};
+use crate::math_utils::consts::PI;
+use crate::math_utils::{FloatType, transform_homogeneous, perspective_divide};
 use crate::transform::Transform;
-use crate::math_utils::FloatType;
 
-const ASPECT_RATIO : FloatType = 16.0 / 9.0;
+// Only used until the camera is told the size of the buffer it renders to
+const DEFAULT_ASPECT_RATIO : FloatType = 16.0 / 9.0;
+const DEFAULT_ORTHOGRAPHIC_VIEW_HEIGHT: FloatType = 10.0;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionType {
+    Perspective,
+    // Parallel projection, objects keep their size regardless of their distance
+    Orthographic
+}
+
+// Which FOV stays the same when the aspect ratio changes.
+// The other one is derived from the aspect ratio.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FovMode {
+    Vertical,
+    Horizontal
+}
+
+// How depth values are laid out after projection.
+// `reversed_z` puts the near plane at depth 1 and the far plane at depth 0. Combined with floating
+// point depth, this spreads precision much more evenly across the view distance.
+// `infinite_far_plane` pushes the far plane to infinity (perspective projection only), so
+// nothing is ever clipped for being too far. The far plane distance is then ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DepthMode {
+    #[serde(default)]
+    pub reversed_z: bool,
+    #[serde(default)]
+    pub infinite_far_plane: bool
+}
+
+impl DepthMode {
+    // Depth value of the far plane, which is what depth buffers should be cleared to
+    pub fn far_depth(&self) -> FloatType {
+        if self.reversed_z { 0.0 } else { 1.0 }
+    }
+
+    pub fn near_depth(&self) -> FloatType {
+        if self.reversed_z { 1.0 } else { 0.0 }
+    }
+
+    // Whether a fragment with depth `new_depth` is in front of one with `old_depth`
+    pub fn is_closer(&self, new_depth: FloatType, old_depth: FloatType) -> bool {
+        if self.reversed_z { new_depth > old_depth } else { new_depth < old_depth }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CameraError {
+    #[error("Near plane distance must be greater than 0")]
+    InvalidNearPlane(FloatType),
+    #[error("Far plane distance must be greater than near plane distance")]
+    InvalidFarPlane { near: FloatType, far: FloatType },
+    #[error("FOV angle must be between 0 and 180 degrees (exclusive)")]
+    InvalidFov(FloatType),
+    #[error("Orthographic view height must be greater than 0")]
+    InvalidViewHeight(FloatType),
+    #[error("Projection matrix can't be inverted")]
+    NonInvertibleProjection
+}
 
 pub struct Camera {
     pub transform: Transform,
     near_plane_distance: FloatType,
     far_plane_distance: FloatType,
-    vertical_fov_angle: FloatType,
-    projection_data: ProjectionData
+    fov_angle: FloatType,
+    fov_mode: FovMode,
+    // Width divided by height of the render target
+    aspect_ratio: FloatType,
+    // Height of the view volume in world units, only used by orthographic projection
+    orthographic_view_height: FloatType,
+    projection_type: ProjectionType,
+    depth_mode: DepthMode,
+    // Replaces the projection generated from the fields above when set
+    custom_projection_matrix: Option<Matrix4>,
+    projection_matrix: Matrix4
 }
 
-#[derive(Debug)]
-pub struct ProjectionData(FloatType, FloatType, FloatType, FloatType);
+// Projection matrices map camera space into clip space. After the perspective divide, the
+// visible volume becomes x and y in [-1, 1] and z (depth) in [0, 1], with the near plane at 0
+// (or at 1 with reversed-Z). Camera space looks towards +z, with +x to the right and +y downwards.
+pub fn perspective_projection<T: Into<FloatType> + Copy>(
+    n: T, f: T, fov: T, aspect_ratio: T,
+    fov_mode: FovMode, depth_mode: DepthMode
+) -> Matrix4 {
+    let n = n.into();
+    let f = f.into();
+    let fov = fov.into();
+    let aspect_ratio = aspect_ratio.into();
 
-impl ProjectionData {
-    pub fn generate<T: Into<FloatType> + Copy>(n: T, f: T, fov: T)
-    -> Self {
-        let n = n.into();
-        let f = f.into();
-        let fov = fov.into();
+    let tan_half_pov = (fov * 0.5 * PI/180.0).tan();
+    let (w_scaler, h_scaler) = match fov_mode {
+        FovMode::Vertical => (1.0 / (aspect_ratio * tan_half_pov), 1.0 / tan_half_pov),
+        FovMode::Horizontal => (1.0 / tan_half_pov, aspect_ratio / tan_half_pov)
+    };
+
+    // Depth after the divide is (z_scaler * z + z_offset) / z
+    let near_far_diff = f - n;
+    let (z_scaler, z_offset) = match (depth_mode.reversed_z, depth_mode.infinite_far_plane) {
+        (false, false) => (f / near_far_diff, -(n * f) / near_far_diff),
+        (false, true) => (1.0, -n),
+        (true, false) => (-n / near_far_diff, (n * f) / near_far_diff),
+        (true, true) => (0.0, n)
+    };
+
+    Matrix4::new([
+        [w_scaler, 0.0, 0.0, 0.0],
+        [0.0, h_scaler, 0.0, 0.0],
+        [0.0, 0.0, z_scaler, z_offset],
+        // Copy z into w, which does the perspective divide later
+        [0.0, 0.0, 1.0, 0.0]
+    ])
+}
 
-        let tan_half_pov = (fov * 0.5 * PI/180.0).tan();
-        let near_far_diff = f - n;
-        Self (
-            // width scaler
-            1.0 / (ASPECT_RATIO * tan_half_pov),
-            // height scaler
-            1.0 / tan_half_pov,
-            // z scaler * m1
-            1.0 / near_far_diff,
-            // z repositioning * m2
-            n / near_far_diff
-        )
+// Orthographic projection always has a finite far plane, `depth_mode.infinite_far_plane` is ignored
+pub fn orthographic_projection<T: Into<FloatType> + Copy>(
+    n: T, f: T, view_height: T, aspect_ratio: T,
+    depth_mode: DepthMode
+) -> Matrix4 {
+    let n = n.into();
+    let f = f.into();
+    let view_height = view_height.into();
+    let aspect_ratio = aspect_ratio.into();
+
+    let near_far_diff = f - n;
+    let (z_scaler, z_offset) = if depth_mode.reversed_z {
+        (-1.0 / near_far_diff, f / near_far_diff)
     }
+    else {
+        (1.0 / near_far_diff, -n / near_far_diff)
+    };
+
+    Matrix4::new([
+        [2.0 / (aspect_ratio * view_height), 0.0, 0.0, 0.0],
+        [0.0, 2.0 / view_height, 0.0, 0.0],
+        [0.0, 0.0, z_scaler, z_offset],
+        [0.0, 0.0, 0.0, 1.0]
+    ])
 }
 
 impl Camera {
@@ -49,27 +162,507 @@ impl Camera {
             transform: Transform::default(),
             near_plane_distance: n,
             far_plane_distance: f,
-            vertical_fov_angle: fov,
-            projection_data: ProjectionData::generate(
-                n, f, fov
+            fov_angle: fov,
+            fov_mode: FovMode::Vertical,
+            aspect_ratio: DEFAULT_ASPECT_RATIO,
+            orthographic_view_height: DEFAULT_ORTHOGRAPHIC_VIEW_HEIGHT,
+            projection_type: ProjectionType::Perspective,
+            depth_mode: DepthMode::default(),
+            custom_projection_matrix: None,
+            projection_matrix: perspective_projection(
+                n, f, fov, DEFAULT_ASPECT_RATIO, FovMode::Vertical, DepthMode::default()
             )
         }
     }
 
+    // Rotates the camera in place so that it faces `target`.
+    // `up` is the world direction that should point towards the top of the screen.
+    // Nothing happens if `target` is at the camera's position, or if `up` is parallel to the
+    // viewing direction, since the orientation would be ambiguous.
+    pub fn look_at(&mut self, target: Vector3<FloatType>, up: Vector3<FloatType>) {
+        // Each column is one of the camera's local axes, in world space
+        let Some(rotation) = Matrix3::look_at_rotation(target - self.transform.position(), up) else {
+            return;
+        };
+
+        self.transform.set_rotation(Quaternion::from_rotation_matrix(&rotation));
+        self.transform.update();
+    }
+
+    pub fn aspect_ratio(&self) -> FloatType {
+        self.aspect_ratio
+    }
+
+    // Should be called whenever the render target is resized, otherwise the image gets stretched
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: FloatType) {
+        if self.aspect_ratio == aspect_ratio {
+            return;
+        }
+
+        self.aspect_ratio = aspect_ratio;
+        self.update_projection_matrix();
+    }
+
+    pub fn fov_mode(&self) -> FovMode {
+        self.fov_mode
+    }
+
+    pub fn set_fov_mode(&mut self, fov_mode: FovMode) {
+        self.fov_mode = fov_mode;
+        self.update_projection_matrix();
+    }
+
+    pub fn projection_type(&self) -> ProjectionType {
+        self.projection_type
+    }
+
+    pub fn set_projection_type(&mut self, projection_type: ProjectionType) {
+        self.projection_type = projection_type;
+        self.update_projection_matrix();
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.depth_mode = depth_mode;
+        self.update_projection_matrix();
+    }
+
+    pub fn set_reversed_z(&mut self, reversed_z: bool) {
+        self.set_depth_mode(DepthMode { reversed_z, ..self.depth_mode });
+    }
+
+    pub fn set_infinite_far_plane(&mut self, infinite_far_plane: bool) {
+        self.set_depth_mode(DepthMode { infinite_far_plane, ..self.depth_mode });
+    }
+
+    pub fn orthographic_view_height(&self) -> FloatType {
+        self.orthographic_view_height
+    }
+
+    pub fn set_orthographic_view_height(&mut self, view_height: FloatType) -> Result<(), CameraError> {
+        if view_height.is_nan() || view_height <= 0.0 {
+            return Err(CameraError::InvalidViewHeight(view_height));
+        }
+
+        self.orthographic_view_height = view_height;
+        self.update_projection_matrix();
+        Ok(())
+    }
+
+    pub fn near_plane_distance(&self) -> FloatType {
+        self.near_plane_distance
+    }
+
+    pub fn far_plane_distance(&self) -> FloatType {
+        self.far_plane_distance
+    }
+
+    // The angle is in degrees. Whether it's the vertical or horizontal FOV depends on `fov_mode`.
+    pub fn fov_angle(&self) -> FloatType {
+        self.fov_angle
+    }
+
+    pub fn set_near_plane_distance(&mut self, near: FloatType) -> Result<(), CameraError> {
+        self.set_clip_planes(near, self.far_plane_distance)
+    }
+
+    pub fn set_far_plane_distance(&mut self, far: FloatType) -> Result<(), CameraError> {
+        self.set_clip_planes(self.near_plane_distance, far)
+    }
+
+    // Sets both planes at once, for when the new near plane is beyond the old far plane
+    // (or the other way around)
+    pub fn set_clip_planes(&mut self, near: FloatType, far: FloatType) -> Result<(), CameraError> {
+        if near.is_nan() || near <= 0.0 {
+            return Err(CameraError::InvalidNearPlane(near));
+        }
+        if far.is_nan() || far <= near {
+            return Err(CameraError::InvalidFarPlane { near, far });
+        }
+
+        self.near_plane_distance = near;
+        self.far_plane_distance = far;
+        self.update_projection_matrix();
+        Ok(())
+    }
+
+    pub fn set_fov_angle(&mut self, fov: FloatType) -> Result<(), CameraError> {
+        if fov.is_nan() || fov <= 0.0 || fov >= 180.0 {
+            return Err(CameraError::InvalidFov(fov));
+        }
+
+        self.fov_angle = fov;
+        self.update_projection_matrix();
+        Ok(())
+    }
+
+    pub fn projection_matrix(&self) -> &Matrix4 {
+        &self.projection_matrix
+    }
+
+    // Use `matrix` as the projection instead of the perspective/orthographic ones.
+    // `None` goes back to the projection described by `projection_type`.
+    pub fn set_custom_projection_matrix(&mut self, matrix: Option<Matrix4>) {
+        self.custom_projection_matrix = matrix;
+        self.update_projection_matrix();
+    }
+
+    // The 6 planes bounding the visible volume, in world space, with normals pointing inwards.
+    // Order is left, right, top, bottom, near, far.
+    // With an infinite far plane, the far plane has a zero normal and every point is inside it.
+    pub fn frustum_planes(&self) -> [Plane; 6] {
+        // Gribb-Hartmann extraction: each clip volume inequality (e.g. -w <= x) is a plane
+        // when written in terms of the rows of (projection * view)
+        let m = &self.projection_matrix * self.transform.world_to_local_matrix();
+        let plane = |coefficient: [FloatType; 4]| Plane::from_coefficients(
+            coefficient[0], coefficient[1], coefficient[2], coefficient[3]
+        );
+        let combine = |sign_a: FloatType, a: usize, sign_b: FloatType, b: usize| -> [FloatType; 4] {
+            [0, 1, 2, 3].map(|col| sign_a * m[a][col] + sign_b * m[b][col])
+        };
+
+        // 0 <= z and z <= w
+        let mut depth_planes = [plane(m[2]), plane(combine(1.0, 3, -1.0, 2))];
+        if self.depth_mode.reversed_z {
+            depth_planes.reverse();
+        }
+
+        [
+            plane(combine(1.0, 3, 1.0, 0)),
+            plane(combine(1.0, 3, -1.0, 0)),
+            // Camera space +y points downwards, so y = -w is the top of the screen
+            plane(combine(1.0, 3, 1.0, 1)),
+            plane(combine(1.0, 3, -1.0, 1)),
+            depth_planes[0],
+            depth_planes[1]
+        ]
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::new(self.frustum_planes())
+    }
+
+    // False only if the sphere (in world space) is entirely outside of the view frustum
+    pub fn is_sphere_visible(&self, sphere: &Sphere) -> bool {
+        self.frustum().intersects_sphere(sphere)
+    }
+
+    // Ray (in world space) going from the near plane through the pixel position (`x`, `y`) of a
+    // render target with size `buffer_size`. The direction is normalized.
+    pub fn screen_point_to_ray(
+        &self, x: FloatType, y: FloatType,
+        buffer_size: Vector2<usize>
+    ) -> Result<Ray, CameraError> {
+        let inverse_projection = self.projection_matrix.invert()
+            .map_err(|_| CameraError::NonInvertibleProjection)?;
+
+        let ncd_x = (x / buffer_size.x() as FloatType) * 2.0 - 1.0;
+        let ncd_y = (y / buffer_size.y() as FloatType) * 2.0 - 1.0;
+        let unproject = |depth: FloatType| {
+            let cam_pos = perspective_divide(transform_homogeneous(
+                &inverse_projection,
+                Vector4::new([ncd_x, ncd_y, depth, 1.0])
+            ));
+            self.transform.local_to_world(cam_pos)
+        };
+
+        // Any second depth between the near and far plane works, as long as it's not at
+        // infinity (depth of 1 with an infinite far plane)
+        let near_depth = self.depth_mode.near_depth();
+        let origin = unproject(near_depth);
+        let direction = unproject((near_depth + 0.5) * 0.5) - origin;
+
+        Ok(Ray::new(origin, direction.normalized()))
+    }
+
+    pub fn project_to_clip_space(&self, point: Vector3<FloatType>) -> Vector4<FloatType> {
+        transform_homogeneous(&self.projection_matrix, point.extend(1.0))
+    }
+
     pub fn project_to_ncd_space(&self, point: Vector3<FloatType>) -> Vector3<FloatType> {
-        let ProjectionData(w_scaler, h_scaler, z_1, z_2) = self.projection_data;
-        Vector3::new([
-            (point.x() * w_scaler) / point.z(),
-            (point.y() * h_scaler) / point.z(),
-            ((point.z() * z_1) + z_2) / point.z()
-        ])
-    }
-
-    pub fn update_projection_data(&mut self) {
-        self.projection_data = ProjectionData::generate(
-            self.near_plane_distance,
-            self.far_plane_distance,
-            self.vertical_fov_angle
+        perspective_divide(self.project_to_clip_space(point))
+    }
+
+    fn update_projection_matrix(&mut self) {
+        if let Some(matrix) = &self.custom_projection_matrix {
+            self.projection_matrix = matrix.clone();
+            return;
+        }
+
+        self.projection_matrix = match self.projection_type {
+            ProjectionType::Perspective => perspective_projection(
+                self.near_plane_distance,
+                self.far_plane_distance,
+                self.fov_angle,
+                self.aspect_ratio,
+                self.fov_mode,
+                self.depth_mode
+            ),
+            ProjectionType::Orthographic => orthographic_projection(
+                self.near_plane_distance,
+                self.far_plane_distance,
+                self.orthographic_view_height,
+                self.aspect_ratio,
+                self.depth_mode
+            )
+        };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::math_utils::vector::{vector, Vector};
+    use crate::math_utils::vector::tests::approx_cmp_vector;
+    use float_cmp::ApproxEq;
+    use crate::math_utils::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn test_perspective_shrinks_with_distance() {
+        let camera = Camera::new(1.0, 100.0, 90.0);
+        let near = camera.project_to_ncd_space(vector![1.0, 1.0, 2.0]);
+        let far = camera.project_to_ncd_space(vector![1.0, 1.0, 4.0]);
+
+        approx_cmp_vector(vector![near.x() * 0.5, near.y() * 0.5], vector![far.x(), far.y()]);
+    }
+
+    #[test]
+    fn test_orthographic_ignores_distance() {
+        let mut camera = Camera::new(1.0, 11.0, 60.0);
+        camera.set_projection_type(ProjectionType::Orthographic);
+        camera.set_orthographic_view_height(4.0).unwrap();
+
+        let near = camera.project_to_ncd_space(vector![1.0, 2.0, 1.0]);
+        let far = camera.project_to_ncd_space(vector![1.0, 2.0, 11.0]);
+
+        approx_cmp_vector(near, vector![0.5 / DEFAULT_ASPECT_RATIO, 1.0, 0.0]);
+        approx_cmp_vector(far, vector![0.5 / DEFAULT_ASPECT_RATIO, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_vertical_fov_mode_keeps_height() {
+        let mut camera = Camera::new(1.0, 100.0, 90.0);
+        camera.set_aspect_ratio(2.0);
+
+        // tan(45 deg) = 1, so the edge of a 90 deg FOV is at y = z
+        let ncd_pos = camera.project_to_ncd_space(vector![1.0, 1.0, 1.0]);
+        approx_cmp_vector(vector![ncd_pos.x(), ncd_pos.y()], vector![0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_horizontal_fov_mode_keeps_width() {
+        let mut camera = Camera::new(1.0, 100.0, 90.0);
+        camera.set_fov_mode(FovMode::Horizontal);
+        camera.set_aspect_ratio(2.0);
+
+        let ncd_pos = camera.project_to_ncd_space(vector![1.0, 0.5, 1.0]);
+        approx_cmp_vector(vector![ncd_pos.x(), ncd_pos.y()], vector![1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_perspective_depth_range() {
+        let camera = Camera::new(1.0, 100.0, 60.0);
+
+        let near = camera.project_to_ncd_space(vector![0.0, 0.0, 1.0]);
+        let far = camera.project_to_ncd_space(vector![0.0, 0.0, 100.0]);
+        approx_cmp_vector(near, vector![0.0, 0.0, 0.0]);
+        approx_cmp_vector(far, vector![0.0, 0.0, 1.0]);
+
+        // w holds the distance from the camera before the divide
+        let clip_pos = camera.project_to_clip_space(vector![0.0, 0.0, 50.0]);
+        assert_eq!(clip_pos[3], 50.0);
+    }
+
+    #[test]
+    fn test_custom_projection_matrix() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_custom_projection_matrix(Some(Matrix4::identity_matrix()));
+        // Custom projection should survive regenerating the projection
+        camera.set_aspect_ratio(1.0);
+
+        let ncd_pos = camera.project_to_ncd_space(vector![0.5, -0.5, 0.25]);
+        approx_cmp_vector(ncd_pos, vector![0.5, -0.5, 0.25]);
+
+        camera.set_custom_projection_matrix(None);
+        assert_eq!(*camera.projection_matrix(), perspective_projection(1.0, 100.0, 60.0, 1.0, FovMode::Vertical, DepthMode::default()));
+    }
+
+    #[test]
+    fn test_look_at() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.transform.set_position(vector![6.0, 0.0, -8.0]);
+        camera.look_at(vector![0.0, 0.0, 0.0], vector![0.0, 1.0, 0.0]);
+
+        // The target is straight ahead of the camera
+        let target_in_camera = camera.transform.world_to_local(vector![0.0, 0.0, 0.0]);
+        assert!(target_in_camera.approx_eq(vector![0.0, 0.0, 10.0], (1e-5, 4)));
+
+        // World up is the top of the screen, which is -y in camera space
+        let above_target = camera.transform.world_to_local(vector![0.0, 1.0, 0.0]);
+        assert!(above_target.y() < 0.0);
+        assert!(above_target.x().abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_setters_update_projection() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_clip_planes(2.0, 50.0).unwrap();
+        camera.set_fov_angle(90.0).unwrap();
+
+        assert_eq!(
+            *camera.projection_matrix(),
+            perspective_projection(2.0, 50.0, 90.0, DEFAULT_ASPECT_RATIO, FovMode::Vertical, DepthMode::default())
         );
     }
+
+    #[test]
+    fn test_invalid_clip_planes() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+
+        assert_eq!(camera.set_near_plane_distance(0.0), Err(CameraError::InvalidNearPlane(0.0)));
+        assert_eq!(camera.set_near_plane_distance(-1.0), Err(CameraError::InvalidNearPlane(-1.0)));
+        assert_eq!(
+            camera.set_near_plane_distance(100.0),
+            Err(CameraError::InvalidFarPlane { near: 100.0, far: 100.0 })
+        );
+        assert_eq!(
+            camera.set_far_plane_distance(0.5),
+            Err(CameraError::InvalidFarPlane { near: 1.0, far: 0.5 })
+        );
+
+        // Failed calls should leave the camera untouched
+        assert_eq!(camera.near_plane_distance(), 1.0);
+        assert_eq!(camera.far_plane_distance(), 100.0);
+        assert_eq!(
+            *camera.projection_matrix(),
+            perspective_projection(1.0, 100.0, 60.0, DEFAULT_ASPECT_RATIO, FovMode::Vertical, DepthMode::default())
+        );
+    }
+
+    #[test]
+    fn test_invalid_fov() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+
+        assert_eq!(camera.set_fov_angle(0.0), Err(CameraError::InvalidFov(0.0)));
+        assert_eq!(camera.set_fov_angle(180.0), Err(CameraError::InvalidFov(180.0)));
+        assert!(camera.set_fov_angle(FloatType::NAN).is_err());
+        assert_eq!(camera.fov_angle(), 60.0);
+    }
+
+    #[test]
+    fn test_reversed_z_depth_range() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_reversed_z(true);
+
+        approx_cmp_vector(camera.project_to_ncd_space(vector![0.0, 0.0, 1.0]), vector![0.0, 0.0, 1.0]);
+        approx_cmp_vector(camera.project_to_ncd_space(vector![0.0, 0.0, 100.0]), vector![0.0, 0.0, 0.0]);
+
+        camera.set_projection_type(ProjectionType::Orthographic);
+        approx_cmp_vector(camera.project_to_ncd_space(vector![0.0, 0.0, 1.0]), vector![0.0, 0.0, 1.0]);
+        approx_cmp_vector(camera.project_to_ncd_space(vector![0.0, 0.0, 100.0]), vector![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_infinite_far_plane() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_infinite_far_plane(true);
+
+        // Way past the far plane distance, but still in front of the far plane
+        let depth = camera.project_to_ncd_space(vector![0.0, 0.0, 1e6]).z();
+        assert!(depth < 1.0 && depth > 0.99);
+        assert_eq!(camera.project_to_ncd_space(vector![0.0, 0.0, 1.0]).z(), 0.0);
+
+        camera.set_reversed_z(true);
+        let depth = camera.project_to_ncd_space(vector![0.0, 0.0, 1e6]).z();
+        assert!(depth > 0.0 && depth < 0.01);
+        assert_eq!(camera.project_to_ncd_space(vector![0.0, 0.0, 1.0]).z(), 1.0);
+    }
+
+    #[test]
+    fn test_depth_comparison() {
+        let standard = DepthMode::default();
+        let reversed = DepthMode { reversed_z: true, infinite_far_plane: false };
+
+        assert!(standard.is_closer(0.2, 0.5));
+        assert!(!standard.is_closer(0.5, standard.near_depth()));
+        assert!(reversed.is_closer(0.5, 0.2));
+        assert!(reversed.is_closer(0.5, reversed.far_depth()));
+    }
+
+    #[test]
+    fn test_frustum_planes() {
+        let mut camera = Camera::new(1.0, 100.0, 90.0);
+        camera.set_aspect_ratio(1.0);
+        camera.transform.set_position(vector![0.0, 0.0, -10.0]);
+        camera.transform.update();
+
+        let [left, right, top, bottom, near, far] = camera.frustum_planes();
+        // In world space, the camera looks along +z starting from z = -10
+        assert!((near.signed_distance(vector![0.0, 0.0, -9.0])).abs() < 1e-5);
+        // Extracting the far plane involves subtracting two close numbers, so it's less precise
+        assert!((far.signed_distance(vector![0.0, 0.0, 90.0])).abs() < 1e-3);
+        // 90 degrees FOV, so the side planes are at 45 degrees
+        let inside = vector![0.0, 0.0, 0.0];
+        for plane in [left, right, top, bottom] {
+            assert!((plane.signed_distance(inside) - 10.0 * FRAC_1_SQRT_2).abs() < 1e-4);
+        }
+        assert!(right.signed_distance(vector![11.0, 0.0, 0.0]) < 0.0);
+        assert!(bottom.signed_distance(vector![0.0, 11.0, 0.0]) < 0.0);
+    }
+
+    #[test]
+    fn test_sphere_visibility() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_reversed_z(true);
+
+        assert!(camera.is_sphere_visible(&Sphere::new(vector![0.0, 0.0, 50.0], 1.0)));
+        // Behind the camera, past the far plane, and far to the side
+        assert!(!camera.is_sphere_visible(&Sphere::new(vector![0.0, 0.0, -5.0], 1.0)));
+        assert!(!camera.is_sphere_visible(&Sphere::new(vector![0.0, 0.0, 150.0], 1.0)));
+        assert!(!camera.is_sphere_visible(&Sphere::new(vector![500.0, 0.0, 50.0], 1.0)));
+        // Partially visible
+        assert!(camera.is_sphere_visible(&Sphere::new(vector![0.0, 0.0, 100.5], 1.0)));
+
+        camera.set_infinite_far_plane(true);
+        assert!(camera.is_sphere_visible(&Sphere::new(vector![0.0, 0.0, 1e6], 1.0)));
+    }
+
+    #[test]
+    fn test_screen_point_to_ray() {
+        let mut camera = Camera::new(1.0, 100.0, 90.0);
+        camera.set_aspect_ratio(2.0);
+        let buffer_size = Vector2::new([200, 100]);
+
+        let ray = camera.screen_point_to_ray(100.0, 50.0, buffer_size).unwrap();
+        assert!(ray.origin.approx_eq(vector![0.0, 0.0, 1.0], (1e-5, 4)));
+        assert!(ray.direction.approx_eq(vector![0.0, 0.0, 1.0], (1e-5, 4)));
+
+        // Right edge of the screen, 90 degrees vertical FOV with an aspect ratio of 2
+        let ray = camera.screen_point_to_ray(200.0, 50.0, buffer_size).unwrap();
+        assert!(ray.origin.approx_eq(vector![2.0, 0.0, 1.0], (1e-5, 4)));
+
+        camera.set_depth_mode(DepthMode { reversed_z: true, infinite_far_plane: true });
+        let ray = camera.screen_point_to_ray(100.0, 100.0, buffer_size).unwrap();
+        assert!(ray.origin.approx_eq(vector![0.0, 1.0, 1.0], (1e-5, 4)));
+        assert!(ray.direction.approx_eq(vector![0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_orthographic_screen_point_to_ray() {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.set_aspect_ratio(1.0);
+        camera.set_projection_type(ProjectionType::Orthographic);
+        camera.set_orthographic_view_height(4.0).unwrap();
+        camera.transform.set_position(vector![0.0, 0.0, -10.0]);
+        camera.transform.update();
+
+        let ray = camera.screen_point_to_ray(0.0, 0.0, Vector2::new([100, 100])).unwrap();
+        assert!(ray.origin.approx_eq(vector![-2.0, -2.0, -9.0], (1e-5, 4)));
+        assert!(ray.direction.approx_eq(vector![0.0, 0.0, 1.0], (1e-5, 4)));
+    }
 }
@@ -0,0 +1,274 @@
+use thiserror::Error;
+
+use crate::animation::catmull_rom;
+use crate::camera::Camera;
+use crate::math_utils::FloatType;
+use crate::math_utils::quaternion::Quaternion;
+use crate::math_utils::vector::Vector3;
+
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CameraPathError {
+    #[error("Camera path needs at least one keyframe")]
+    NoKeyframes,
+
+    #[error("Frames per second must be greater than 0, got {0}")]
+    InvalidFrameRate(FloatType)
+}
+
+// Where the camera is, and where it faces, at `time` seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub time: FloatType,
+    pub position: Vector3<FloatType>,
+    pub rotation: Quaternion
+}
+
+impl CameraKeyframe {
+    pub fn new(time: FloatType, position: Vector3<FloatType>, rotation: Quaternion) -> Self {
+        Self { time, position, rotation }
+    }
+}
+
+// Moves the camera through a list of keyframes. Positions follow a Catmull-Rom spline, so the
+// camera passes through every keyframe without sudden changes of direction, while rotations are
+// slerped between the two surrounding keyframes.
+// A looping path wraps around to its first keyframe after the last one, so it should end on the
+// same position and rotation it starts with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraPath {
+    // Sorted by time
+    keyframes: Vec<CameraKeyframe>,
+    is_looping: bool
+}
+
+impl CameraPath {
+    pub fn new(mut keyframes: Vec<CameraKeyframe>, is_looping: bool) -> Result<Self, CameraPathError> {
+        if keyframes.is_empty() {
+            return Err(CameraPathError::NoKeyframes);
+        }
+
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(Self { keyframes, is_looping })
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.is_looping
+    }
+
+    pub fn start_time(&self) -> FloatType {
+        self.keyframes[0].time
+    }
+
+    pub fn end_time(&self) -> FloatType {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    pub fn duration(&self) -> FloatType {
+        self.end_time() - self.start_time()
+    }
+
+    // Position and rotation of the camera at `time` seconds.
+    // Outside of the keyframes, a looping path wraps around while other paths hold still on
+    // their first or last keyframe.
+    pub fn sample(&self, time: FloatType) -> (Vector3<FloatType>, Quaternion) {
+        let last = self.keyframes.len() - 1;
+        if last == 0 {
+            let keyframe = self.keyframes[0];
+            return (keyframe.position, keyframe.rotation);
+        }
+
+        // Index of the keyframe ending the segment `time` is in
+        let time = self.wrap_time(time);
+        let end = self.keyframes.partition_point(|keyframe| keyframe.time <= time).clamp(1, last);
+
+        let start = end - 1;
+        let (t1, p1) = (self.keyframes[start].time, self.keyframes[start].position);
+        let (t2, p2) = (self.keyframes[end].time, self.keyframes[end].position);
+        let (t0, p0) = self.neighbour_before(start);
+        let (t3, p3) = self.neighbour_after(end);
+
+        let segment_duration = t2 - t1;
+        let u = if segment_duration > 0.0 { (time - t1) / segment_duration } else { 0.0 };
+
+        let position = catmull_rom(
+            [p0, p1, p2, p3],
+            [t0, t1, t2, t3],
+            u
+        );
+        let rotation = self.keyframes[start].rotation.slerp(self.keyframes[end].rotation, u);
+
+        (position, rotation)
+    }
+
+    pub fn apply(&self, camera: &mut Camera, time: FloatType) {
+        let (position, rotation) = self.sample(time);
+        camera.transform.set_position(position);
+        camera.transform.set_rotation(rotation);
+        camera.transform.update();
+    }
+
+    // Times of every frame of the path, for rendering it at a fixed frame rate.
+    // The frame at the end of a looping path is left out, since it is the same as the first one.
+    pub fn frame_times(&self, frames_per_second: FloatType)
+        -> Result<impl Iterator<Item = FloatType>, CameraPathError> {
+        if frames_per_second <= 0.0 || !frames_per_second.is_finite() {
+            return Err(CameraPathError::InvalidFrameRate(frames_per_second));
+        }
+
+        let start_time = self.start_time();
+        let whole_frames = (self.duration() * frames_per_second).floor() as usize;
+        let frame_count = if self.is_looping && whole_frames > 0 { whole_frames } else { whole_frames + 1 };
+
+        Ok((0..frame_count).map(move |frame| start_time + frame as FloatType / frames_per_second))
+    }
+
+    fn wrap_time(&self, time: FloatType) -> FloatType {
+        let duration = self.duration();
+        if self.is_looping && duration > 0.0 {
+            self.start_time() + (time - self.start_time()).rem_euclid(duration)
+        }
+        else {
+            time.clamp(self.start_time(), self.end_time())
+        }
+    }
+
+    // The keyframe before `index`, used to shape the curve. Ends of a non-looping path repeat
+    // their end keyframe, while a looping path takes it from the other end (skipping the last
+    // keyframe, which doubles the first).
+    fn neighbour_before(&self, index: usize) -> (FloatType, Vector3<FloatType>) {
+        if index > 0 {
+            let keyframe = self.keyframes[index - 1];
+            return (keyframe.time, keyframe.position);
+        }
+
+        let keyframe = self.keyframes[0];
+        if self.is_looping && self.keyframes.len() > 2 {
+            let wrapped = self.keyframes[self.keyframes.len() - 2];
+            return (wrapped.time - self.duration(), wrapped.position);
+        }
+        (keyframe.time, keyframe.position)
+    }
+
+    fn neighbour_after(&self, index: usize) -> (FloatType, Vector3<FloatType>) {
+        let last = self.keyframes.len() - 1;
+        if index < last {
+            let keyframe = self.keyframes[index + 1];
+            return (keyframe.time, keyframe.position);
+        }
+
+        let keyframe = self.keyframes[last];
+        if self.is_looping && self.keyframes.len() > 2 {
+            let wrapped = self.keyframes[1];
+            return (wrapped.time + self.duration(), wrapped.position);
+        }
+        (keyframe.time, keyframe.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use float_cmp::ApproxEq;
+    use crate::math_utils::vector::{vector, Vector};
+    use crate::math_utils::vector::tests::approx_cmp_vector;
+    use crate::math_utils::quaternion::tests::approx_cmp_quaternion;
+    use crate::math_utils::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn init_test_path(is_looping: bool) -> CameraPath {
+        let turn = Quaternion::from_euler_angles(0.0, FRAC_PI_2, 0.0);
+        CameraPath::new(vec![
+            CameraKeyframe::new(2.0, vector![4.0, 0.0, 0.0], turn),
+            CameraKeyframe::new(0.0, vector![0.0, 0.0, 0.0], Quaternion::default()),
+            CameraKeyframe::new(1.0, vector![2.0, 2.0, 0.0], Quaternion::default()),
+            CameraKeyframe::new(3.0, vector![0.0, 0.0, 0.0], Quaternion::default())
+        ], is_looping).unwrap()
+    }
+
+    #[test]
+    fn test_empty_path() {
+        assert_eq!(CameraPath::new(vec![], false), Err(CameraPathError::NoKeyframes));
+    }
+
+    #[test]
+    fn test_path_passes_through_keyframes() {
+        let path = init_test_path(false);
+        assert_eq!(path.duration(), 3.0);
+
+        for keyframe in path.keyframes() {
+            let (position, rotation) = path.sample(keyframe.time);
+            approx_cmp_vector(position, keyframe.position);
+            approx_cmp_quaternion(rotation, keyframe.rotation);
+        }
+    }
+
+    #[test]
+    fn test_path_is_smooth() {
+        let path = init_test_path(false);
+
+        // Catmull-Rom tangent at the middle keyframe points from its neighbours' positions
+        let (before, _) = path.sample(0.999);
+        let (after, _) = path.sample(1.001);
+        let direction = (after - before).normalized();
+        assert!(direction.approx_eq(vector![1.0, 0.0, 0.0], (1e-2, 4)));
+    }
+
+    #[test]
+    fn test_path_slerps_rotation() {
+        let path = init_test_path(false);
+        let (_, rotation) = path.sample(1.5);
+
+        assert!(rotation.approx_eq(
+            Quaternion::from_euler_angles(0.0, FRAC_PI_4, 0.0),
+            (1e-5, 4)
+        ));
+    }
+
+    #[test]
+    fn test_path_outside_of_keyframes() {
+        let path = init_test_path(false);
+        approx_cmp_vector(path.sample(-1.0).0, vector![0.0, 0.0, 0.0]);
+        approx_cmp_vector(path.sample(10.0).0, vector![0.0, 0.0, 0.0]);
+
+        let looping_path = init_test_path(true);
+        approx_cmp_vector(looping_path.sample(4.0).0, vector![2.0, 2.0, 0.0]);
+        approx_cmp_vector(looping_path.sample(-2.0).0, vector![2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_single_keyframe_path() {
+        let path = CameraPath::new(vec![
+            CameraKeyframe::new(1.0, vector![1.0, 2.0, 3.0], Quaternion::default())
+        ], true).unwrap();
+
+        approx_cmp_vector(path.sample(5.0).0, vector![1.0, 2.0, 3.0]);
+        assert_eq!(path.frame_times(30.0).unwrap().collect::<Vec<_>>(), vec![1.0]);
+    }
+
+    #[test]
+    fn test_frame_times() {
+        let path = init_test_path(false);
+        let times: Vec<_> = path.frame_times(2.0).unwrap().collect();
+        assert_eq!(times, vec![0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0]);
+
+        // The last frame of a loop would repeat the first
+        let looping_path = init_test_path(true);
+        assert_eq!(looping_path.frame_times(2.0).unwrap().count(), 6);
+
+        assert!(matches!(path.frame_times(0.0), Err(CameraPathError::InvalidFrameRate(_))));
+    }
+
+    #[test]
+    fn test_apply_path_to_camera() {
+        let path = init_test_path(false);
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        path.apply(&mut camera, 1.0);
+
+        approx_cmp_vector(camera.transform.position(), vector![2.0, 2.0, 0.0]);
+    }
+}
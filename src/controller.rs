@@ -0,0 +1,319 @@
+use crate::camera::Camera;
+use crate::math_utils::{consts, FloatType};
+use crate::math_utils::vector::Vector3;
+
+const WORLD_UP: [FloatType; 3] = [0.0, 1.0, 0.0];
+
+// Keeps the camera from looking straight up or down, where `look_at` would not be able to tell
+// which way is up
+const MAX_PITCH: FloatType = 89.0 * (consts::PI / 180.0);
+const MIN_ORBIT_DISTANCE: FloatType = 0.01;
+
+// Moves the camera on a sphere around `target`, while always facing it.
+// With yaw and pitch of 0, the camera sits on the -z side of the target, looking towards +z.
+// Positive pitch moves the camera upwards (+y).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitController {
+    target: Vector3<FloatType>,
+    yaw: FloatType,
+    pitch: FloatType,
+    distance: FloatType,
+    // Radians per second added to yaw on each `update`
+    yaw_speed: FloatType
+}
+
+impl OrbitController {
+    pub fn new(target: Vector3<FloatType>, yaw: FloatType, pitch: FloatType, distance: FloatType) -> Self {
+        Self {
+            target,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            distance: distance.max(MIN_ORBIT_DISTANCE),
+            yaw_speed: 0.0
+        }
+    }
+
+    pub fn with_yaw_speed(mut self, yaw_speed: FloatType) -> Self {
+        self.yaw_speed = yaw_speed;
+        self
+    }
+
+    pub fn target(&self) -> Vector3<FloatType> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Vector3<FloatType>) {
+        self.target = target;
+    }
+
+    pub fn yaw(&self) -> FloatType {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> FloatType {
+        self.pitch
+    }
+
+    pub fn distance(&self) -> FloatType {
+        self.distance
+    }
+
+    pub fn rotate(&mut self, delta_yaw: FloatType, delta_pitch: FloatType) {
+        self.yaw += delta_yaw;
+        self.pitch = (self.pitch + delta_pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn zoom(&mut self, delta_distance: FloatType) {
+        self.distance = (self.distance + delta_distance).max(MIN_ORBIT_DISTANCE);
+    }
+
+    // Advance the automatic orbit by `delta_time` seconds
+    pub fn update(&mut self, delta_time: FloatType) {
+        self.rotate(self.yaw_speed * delta_time, 0.0);
+    }
+
+    pub fn camera_position(&self) -> Vector3<FloatType> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        self.target + Vector3::new([
+            self.distance * cos_pitch * sin_yaw,
+            self.distance * sin_pitch,
+            -self.distance * cos_pitch * cos_yaw
+        ])
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.transform.set_position(self.camera_position());
+        camera.look_at(self.target, Vector3::new(WORLD_UP));
+    }
+}
+
+// Movement keys understood by `FlyController`. Mapping physical keys to these is left to the
+// windowing code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlyKey {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+impl FlyKey {
+    const COUNT: usize = 6;
+}
+
+// First-person camera control. Keys move the camera relative to where it faces (up and down
+// are along world +y), while mouse movement turns it. The camera never rolls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyController {
+    // Units per second
+    move_speed: FloatType,
+    // Radians per unit of mouse movement
+    mouse_sensitivity: FloatType,
+
+    pressed_keys: [bool; FlyKey::COUNT],
+    is_mouse_look_active: bool,
+    mouse_delta: (FloatType, FloatType)
+}
+
+impl FlyController {
+    pub fn new(move_speed: FloatType, mouse_sensitivity: FloatType) -> Self {
+        Self {
+            move_speed,
+            mouse_sensitivity,
+            pressed_keys: [false; FlyKey::COUNT],
+            is_mouse_look_active: false,
+            mouse_delta: (0.0, 0.0)
+        }
+    }
+
+    pub fn set_key(&mut self, key: FlyKey, is_pressed: bool) {
+        self.pressed_keys[key as usize] = is_pressed;
+    }
+
+    pub fn is_key_pressed(&self, key: FlyKey) -> bool {
+        self.pressed_keys[key as usize]
+    }
+
+    // Mouse movement only turns the camera while mouse-look is active
+    pub fn set_mouse_look(&mut self, is_active: bool) {
+        self.is_mouse_look_active = is_active;
+    }
+
+    // Positive x is to the right, positive y is downwards, like screen coordinates.
+    // Deltas are accumulated until the next `update`.
+    pub fn add_mouse_delta(&mut self, dx: FloatType, dy: FloatType) {
+        if !self.is_mouse_look_active {
+            return;
+        }
+
+        self.mouse_delta.0 += dx;
+        self.mouse_delta.1 += dy;
+    }
+
+    pub fn has_input(&self) -> bool {
+        self.pressed_keys.iter().any(|pressed| *pressed) || self.mouse_delta != (0.0, 0.0)
+    }
+
+    // Apply the input gathered since the last call, `delta_time` being in seconds.
+    // The camera is left untouched if there was no input.
+    pub fn update(&mut self, camera: &mut Camera, delta_time: FloatType) {
+        if !self.has_input() {
+            return;
+        }
+
+        // Start from wherever the camera currently faces, so other code is free to move it too
+        let position = camera.transform.position();
+        let forward = camera.transform.local_to_world(Vector3::new([0.0, 0.0, 1.0])) - position;
+        let mut yaw = forward.x().atan2(forward.z());
+        let mut pitch = forward.y().clamp(-1.0, 1.0).asin();
+
+        // Moving the mouse to the right turns right, which is towards -yaw
+        yaw -= self.mouse_delta.0 * self.mouse_sensitivity;
+        pitch = (pitch - self.mouse_delta.1 * self.mouse_sensitivity)
+            .clamp(-MAX_PITCH, MAX_PITCH);
+        self.mouse_delta = (0.0, 0.0);
+
+        let (sin_yaw, cos_yaw) = yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = pitch.sin_cos();
+        let forward = Vector3::new([cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw]);
+        let up = Vector3::new(WORLD_UP);
+        let right = forward.cross(up).normalized();
+
+        let mut direction = Vector3::<FloatType>::default();
+        let axes = [
+            (FlyKey::Forward, forward),
+            (FlyKey::Backward, -forward),
+            (FlyKey::Right, right),
+            (FlyKey::Left, -right),
+            (FlyKey::Up, up),
+            (FlyKey::Down, -up)
+        ];
+        for (key, axis) in axes {
+            if self.is_key_pressed(key) {
+                direction += axis;
+            }
+        }
+
+        // Moving diagonally should not be faster
+        let step = self.move_speed * delta_time;
+        let direction = direction.normalized();
+        let new_position = position + direction * step;
+
+        camera.transform.set_position(new_position);
+        camera.look_at(new_position + forward, up);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use float_cmp::ApproxEq;
+    use crate::math_utils::vector::{vector, Vector};
+    use crate::math_utils::vector::tests::approx_cmp_vector;
+
+    #[test]
+    fn test_orbit_camera_position() {
+        let orbit = OrbitController::new(vector![1.0, 2.0, 3.0], 0.0, 0.0, 5.0);
+        approx_cmp_vector(orbit.camera_position(), vector![1.0, 2.0, -2.0]);
+
+        let orbit = OrbitController::new(vector![0.0, 0.0, 0.0], consts::FRAC_PI_2, 0.0, 5.0);
+        assert!(orbit.camera_position().approx_eq(vector![5.0, 0.0, 0.0], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_orbit_pitch_is_clamped() {
+        let mut orbit = OrbitController::new(vector![0.0, 0.0, 0.0], 0.0, 0.0, 5.0);
+        orbit.rotate(0.0, 10.0);
+
+        assert_eq!(orbit.pitch(), MAX_PITCH);
+    }
+
+    #[test]
+    fn test_orbit_update_uses_yaw_speed() {
+        let mut orbit = OrbitController::new(vector![0.0, 0.0, 0.0], 0.0, 0.0, 5.0)
+            .with_yaw_speed(2.0);
+        orbit.update(0.25);
+
+        assert_eq!(orbit.yaw(), 0.5);
+    }
+
+    fn init_fly_test_camera() -> Camera {
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        camera.look_at(vector![0.0, 0.0, 1.0], Vector3::new(WORLD_UP));
+        camera
+    }
+
+    #[test]
+    fn test_fly_without_input_keeps_camera() {
+        let mut camera = init_fly_test_camera();
+        camera.transform.set_position(vector![1.0, 2.0, 3.0]);
+        let mut fly = FlyController::new(2.0, 0.01);
+        fly.update(&mut camera, 1.0);
+
+        assert_eq!(camera.transform.position(), vector![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_fly_movement_scales_with_time() {
+        let mut camera = init_fly_test_camera();
+        let mut fly = FlyController::new(2.0, 0.01);
+        fly.set_key(FlyKey::Forward, true);
+        fly.update(&mut camera, 0.5);
+
+        assert!(camera.transform.position().approx_eq(vector![0.0, 0.0, 1.0], (1e-5, 4)));
+
+        fly.set_key(FlyKey::Forward, false);
+        fly.set_key(FlyKey::Up, true);
+        fly.update(&mut camera, 0.25);
+
+        assert!(camera.transform.position().approx_eq(vector![0.0, 0.5, 1.0], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_fly_diagonal_is_not_faster() {
+        let mut camera = init_fly_test_camera();
+        let mut fly = FlyController::new(1.0, 0.01);
+        fly.set_key(FlyKey::Forward, true);
+        fly.set_key(FlyKey::Right, true);
+        fly.update(&mut camera, 1.0);
+
+        assert!((camera.transform.position().length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fly_mouse_look() {
+        let mut camera = init_fly_test_camera();
+        let mut fly = FlyController::new(1.0, 0.01);
+
+        // Ignored, mouse-look is not active yet
+        fly.add_mouse_delta(100.0, 0.0);
+        assert!(!fly.has_input());
+
+        fly.set_mouse_look(true);
+        fly.add_mouse_delta(50.0, 0.0);
+        fly.add_mouse_delta(50.0, 0.0);
+        fly.update(&mut camera, 1.0);
+
+        // Turned right by 1 radian; a point to the camera's right is now straight ahead
+        let yaw: FloatType = -1.0;
+        let ahead = vector![yaw.sin(), 0.0, yaw.cos()];
+        let ahead_in_camera = camera.transform.world_to_local(ahead);
+        assert!(ahead_in_camera.approx_eq(vector![0.0, 0.0, 1.0], (1e-4, 4)));
+    }
+
+    #[test]
+    fn test_orbit_camera_faces_target() {
+        let orbit = OrbitController::new(vector![1.0, 0.0, 4.0], 0.6, 0.3, 5.0);
+        let mut camera = Camera::new(1.0, 100.0, 60.0);
+        orbit.apply(&mut camera);
+
+        let target_in_camera = camera.transform.world_to_local(orbit.target());
+        assert!(target_in_camera.approx_eq(vector![0.0, 0.0, 5.0], (1e-4, 4)));
+    }
+}
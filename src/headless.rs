@@ -0,0 +1,112 @@
+use std::io::{self, Write};
+
+use crate::camera_path::{CameraPath, CameraPathError};
+use crate::math_utils::FloatType;
+use crate::renderer::{Color, RenderType, Renderer, RendererError};
+use crate::scene::Scene;
+
+use thiserror::Error;
+
+
+#[derive(Debug, Error)]
+pub enum HeadlessError {
+    #[error(transparent)]
+    Renderer(#[from] RendererError),
+
+    #[error(transparent)]
+    CameraPath(#[from] CameraPathError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error)
+}
+
+// Renders into a buffer it owns instead of a window, e.g. to save frames to disk
+pub struct HeadlessRenderer {
+    renderer: Renderer,
+    buffer: Vec<u32>,
+    width: usize,
+    height: usize
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut renderer = Renderer::new();
+        renderer.update_buffer_size(width, height);
+
+        Self {
+            renderer,
+            buffer: vec![0; width * height],
+            width,
+            height
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+
+    // Renders the scene from its own camera, which is adjusted to the buffer's aspect ratio
+    pub fn render(&mut self, scene: &mut Scene, render_type: RenderType) -> Result<&[u32], RendererError> {
+        scene.camera.set_aspect_ratio(self.renderer.aspect_ratio());
+
+        self.buffer.fill(0);
+        self.renderer.render(&scene.object, &scene.camera, &mut self.buffer, render_type)?;
+        self.renderer.apply_post_process(&scene.post_process, &mut self.buffer);
+
+        Ok(&self.buffer)
+    }
+
+    // Moves the scene's camera along `path` at a fixed frame rate, calling `on_frame` with the
+    // index and image of every frame. Object animations play from the start of the path.
+    pub fn render_camera_path<F>(
+        &mut self,
+        scene: &mut Scene,
+        path: &CameraPath,
+        frames_per_second: FloatType,
+        render_type: RenderType,
+        mut on_frame: F
+    ) -> Result<(), HeadlessError>
+    where F: FnMut(usize, &[u32]) -> Result<(), HeadlessError> {
+        for (index, time) in path.frame_times(frames_per_second)?.enumerate() {
+            path.apply(&mut scene.camera, time);
+            scene.object.animate(time - path.start_time());
+            scene.object.update_transforms();
+            let frame = self.render(scene, render_type)?;
+            on_frame(index, frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Writes the buffer as a binary PPM image, which almost every image tool can read
+pub fn write_ppm<W: Write>(writer: &mut W, buffer: &[u32], width: usize, height: usize) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    let pixels: Vec<u8> = buffer.iter()
+        .flat_map(|pixel| Color::from_u32(*pixel).rgb())
+        .collect();
+    writer.write_all(&pixels)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_ppm() {
+        let buffer = [Color::from_rgb(255, 0, 10).u32_color(), 0];
+        let mut output = Vec::new();
+        write_ppm(&mut output, &buffer, 2, 1).unwrap();
+
+        assert_eq!(output, b"P6\n2 1\n255\n\xff\x00\x0a\x00\x00\x00");
+    }
+}
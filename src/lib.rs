@@ -1,10 +1,13 @@
 use std::rc::Rc;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
 use std::time::Instant;
 use std::num::NonZeroU32;
-use std::f32::consts::PI;
 
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 use winit::dpi;
@@ -18,11 +21,20 @@ pub mod transform;
 pub mod object;
 pub mod camera;
 pub mod scene;
+pub mod post_process;
+pub mod controller;
+pub mod camera_path;
+pub mod headless;
+pub mod animation;
 
-use crate::math_utils::quaternion::Quaternion;
 use crate::renderer::{RenderType, Renderer};
-use crate::camera::Camera;
+use crate::camera::{Camera, CameraError, FovMode, ProjectionType};
+use crate::controller::{FlyController, FlyKey};
+use crate::math_utils::FloatType;
+use crate::math_utils::vector::Vector2;
+use crate::object::PickHit;
 use crate::scene::Scene;
+use crate::headless::{HeadlessError, HeadlessRenderer, write_ppm};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -30,32 +42,173 @@ pub struct Arguments {
     config_filename: String,
 
     #[arg(short, long)]
-    render_type: renderer::RenderType
+    render_type: renderer::RenderType,
+
+    // The camera options below override the scene file's camera when given
+    #[arg(short, long, value_enum)]
+    projection: Option<ProjectionType>,
+
+    // Height of the visible area in world units, used by orthographic projection
+    #[arg(long, value_parser = parse_view_height)]
+    view_height: Option<FloatType>,
+
+    // Which FOV is kept when the window's aspect ratio changes
+    #[arg(long, value_enum)]
+    fov_mode: Option<FovMode>,
+
+    // Store depth with the near plane at 1 and the far plane at 0, for better precision
+    #[arg(long)]
+    reversed_z: bool,
+
+    // Never clip objects for being too far away
+    #[arg(long)]
+    infinite_far_plane: bool,
+
+    // Render the scene's camera path to numbered PPM files in this directory, without a window
+    #[arg(long)]
+    headless_output: Option<PathBuf>,
+
+    // Frame rate used when rendering the camera path headlessly
+    #[arg(long, default_value_t = 30.0)]
+    fps: FloatType
+}
+
+// Rejects the same values as `Camera::set_orthographic_view_height`, so that a bad value is
+// reported by clap instead of panicking later on
+fn parse_view_height(arg: &str) -> Result<FloatType, String> {
+    let view_height: FloatType = arg.parse().map_err(|error| format!("{error}"))?;
+    if view_height.is_nan() || view_height <= 0.0 {
+        return Err(CameraError::InvalidViewHeight(view_height).to_string());
+    }
+
+    Ok(view_height)
+}
+
+impl Arguments {
+    pub fn is_headless(&self) -> bool {
+        self.headless_output.is_some()
+    }
 }
 
 pub struct App {
     window: Option<Rc<Window>>,
     surface: Option<Surface<Rc<Window>, Rc<Window>>>,
     redraw_count: usize,
+    last_redraw: Instant,
+    start_time: Instant,
 
     scene: Scene,
     renderer: Renderer,
     render_type: RenderType,
-    camera: Camera
+    fly_controller: FlyController,
+    cursor_position: (FloatType, FloatType),
+    // Last left click that hit something, kept to measure the distance to the next one
+    last_pick: Option<PickHit>
+}
+
+const WINDOW_TITLE: &str = "zesty_engine2";
+const WINDOW_WIDTH: u32 = 768;
+const WINDOW_HEIGHT: u32 = 432;
+
+// Units per second, and radians per pixel of mouse movement
+const FLY_MOVE_SPEED: FloatType = 5.0;
+const FLY_MOUSE_SENSITIVITY: FloatType = 0.003;
+
+fn fly_key_from_key_code(key_code: KeyCode) -> Option<FlyKey> {
+    match key_code {
+        KeyCode::KeyW => Some(FlyKey::Forward),
+        KeyCode::KeyS => Some(FlyKey::Backward),
+        KeyCode::KeyA => Some(FlyKey::Left),
+        KeyCode::KeyD => Some(FlyKey::Right),
+        KeyCode::KeyE => Some(FlyKey::Up),
+        KeyCode::KeyQ => Some(FlyKey::Down),
+        _ => None
+    }
+}
+
+// Describes a pick, along with its distance to the previous one so that clicking two points
+// measures them
+fn pick_title(pick: Option<&PickHit>, previous: Option<&PickHit>) -> String {
+    let Some(pick) = pick else {
+        return format!("{WINDOW_TITLE} - nothing picked");
+    };
+
+    let position = pick.hit.position;
+    let mut title = format!(
+        "{WINDOW_TITLE} - object {:?}, face {} at ({:.3}, {:.3}, {:.3})",
+        pick.path, pick.hit.face_index, position.x(), position.y(), position.z()
+    );
+    if let Some(previous) = previous {
+        let distance = (position - previous.hit.position).length();
+        title += &format!(", {distance:.3} from the previous pick");
+    }
+
+    title
+}
+
+fn apply_camera_overrides(args: &Arguments, camera: &mut Camera) {
+    if let Some(view_height) = args.view_height {
+        // Already validated by `parse_view_height`
+        camera.set_orthographic_view_height(view_height).unwrap();
+    }
+    if let Some(projection) = args.projection {
+        camera.set_projection_type(projection);
+    }
+    if let Some(fov_mode) = args.fov_mode {
+        camera.set_fov_mode(fov_mode);
+    }
+    if args.reversed_z {
+        camera.set_reversed_z(true);
+    }
+    if args.infinite_far_plane {
+        camera.set_infinite_far_plane(true);
+    }
+}
+
+fn load_scene(args: &Arguments) -> Scene {
+    let mut scene = Scene::new(&args.config_filename);
+    apply_camera_overrides(args, &mut scene.camera);
+    scene
+}
+
+// Renders every frame of the scene's camera path into `args.headless_output`, named
+// frame_00000.ppm, frame_00001.ppm and so on
+pub fn run_headless(args: Arguments) -> Result<(), HeadlessError> {
+    let output_dir = args.headless_output.clone().unwrap_or_default();
+    let mut scene = load_scene(&args);
+    let Some(path) = scene.camera_path.clone() else {
+        println!("The scene has no camera path; nothing to render");
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(&output_dir)?;
+    let mut renderer = HeadlessRenderer::new(WINDOW_WIDTH as usize, WINDOW_HEIGHT as usize);
+    let (width, height) = (renderer.width(), renderer.height());
+
+    renderer.render_camera_path(&mut scene, &path, args.fps, args.render_type, |index, frame| {
+        let file = File::create(output_dir.join(format!("frame_{:05}.ppm", index)))?;
+        write_ppm(&mut BufWriter::new(file), frame, width, height)?;
+        Ok(())
+    })
 }
 
 impl App {
     pub fn new(args: Arguments) -> Self {
-        let scene = Scene::new(&args.config_filename);
+        let scene = load_scene(&args);
+
         Self {
             window: None,
             surface: None,
             redraw_count: 0,
+            last_redraw: Instant::now(),
+            start_time: Instant::now(),
 
             scene,
             renderer: Renderer::new(),
             render_type: args.render_type,
-            camera: Camera::new(1.0, 100.0, 60.0)
+            fly_controller: FlyController::new(FLY_MOVE_SPEED, FLY_MOUSE_SENSITIVITY),
+            cursor_position: (0.0, 0.0),
+            last_pick: None
         }
     }
 }
@@ -63,8 +216,9 @@ impl App {
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window_attr = Window::default_attributes()
+            .with_title(WINDOW_TITLE)
             .with_resizable(false)
-            .with_inner_size(dpi::LogicalSize::new(768, 432));
+            .with_inner_size(dpi::LogicalSize::new(WINDOW_WIDTH, WINDOW_HEIGHT));
 
         let window = Rc::new(
             event_loop.create_window(
@@ -76,6 +230,8 @@ impl ApplicationHandler for App {
         let context = Context::new(window.clone()).unwrap();
         self.surface = Some(Surface::new(&context, window.clone()).unwrap());
         self.redraw_count = 0;
+        self.last_redraw = Instant::now();
+        self.start_time = Instant::now();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
@@ -84,20 +240,67 @@ impl ApplicationHandler for App {
             return
         }
 
-        let now = Instant::now();
-
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 event_loop.exit();
             },
 
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(key_code) = event.physical_key {
+                    if let Some(key) = fly_key_from_key_code(key_code) {
+                        self.fly_controller.set_key(key, event.state == ElementState::Pressed);
+                    }
+                }
+            },
+
+            // Hold the right mouse button to look around
+            WindowEvent::MouseInput { button: MouseButton::Right, state, .. } => {
+                self.fly_controller.set_mouse_look(state == ElementState::Pressed);
+            },
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = (position.x as FloatType, position.y as FloatType);
+            },
+
+            // Left click picks the point under the cursor, and shows it in the window title
+            WindowEvent::MouseInput { button: MouseButton::Left, state: ElementState::Pressed, .. } => {
+                let size = window_ref.inner_size();
+                let buffer_size = Vector2::new([size.width as usize, size.height as usize]);
+                let (x, y) = self.cursor_position;
+
+                let pick = self.scene.camera.screen_point_to_ray(x, y, buffer_size).ok()
+                    .and_then(|ray| self.scene.object.pick(&ray));
+                window_ref.set_title(&pick_title(pick.as_ref(), self.last_pick.as_ref()));
+                if pick.is_some() {
+                    self.last_pick = pick;
+                }
+            },
+
             WindowEvent::RedrawRequested => {
-                let angle = 0.5 * (PI / 180.0);
-                self.scene.object.transform.rotate(
-                    Quaternion::from_euler_angles(angle, angle, angle)
-                );
-                self.scene.object.transform.update();
+                let now = Instant::now();
+                let delta_time = now.duration_since(self.last_redraw).as_secs_f64() as FloatType;
+                self.last_redraw = now;
+
+                // Manual control takes over from the automatic orbit and camera path
+                if self.fly_controller.has_input() {
+                    self.scene.orbit = None;
+                    self.scene.camera_path = None;
+                }
+
+                if let Some(path) = &self.scene.camera_path {
+                    let elapsed = now.duration_since(self.start_time).as_secs_f64() as FloatType;
+                    path.apply(&mut self.scene.camera, path.start_time() + elapsed);
+                }
+                else if let Some(orbit) = self.scene.orbit.as_mut() {
+                    orbit.update(delta_time);
+                    orbit.apply(&mut self.scene.camera);
+                }
+                self.fly_controller.update(&mut self.scene.camera, delta_time);
+
+                let elapsed = now.duration_since(self.start_time).as_secs_f64() as FloatType;
+                self.scene.object.animate(elapsed);
+                self.scene.object.update_transforms();
                     
                 let (width, height) = {
                     let size = window_ref.inner_size();
@@ -111,6 +314,7 @@ impl ApplicationHandler for App {
                 ).unwrap();
 
                 self.renderer.update_buffer_size(width as usize, height as usize);
+                self.scene.camera.set_aspect_ratio(self.renderer.aspect_ratio());
 
                 let mut buffer = surface_mut_ref.buffer_mut().unwrap();
 
@@ -118,21 +322,68 @@ impl ApplicationHandler for App {
                 buffer.fill(0);
 
                 // Render here
-                self.renderer.render(&self.scene.object, &self.camera, &mut buffer, self.render_type).unwrap();
+                self.renderer.render(&self.scene.object, &self.scene.camera, &mut buffer, self.render_type).unwrap();
+                self.renderer.apply_post_process(&self.scene.post_process, &mut buffer);
                 buffer.present().unwrap();
 
                 self.redraw_count += 1;
-                //println!("Redraw count: {}, FPS: {}",
-                //    self.redraw_count,
-                //    1_000_000 / now.elapsed().as_micros()
-                //);
             },
 
             _ => (),
         }
     }
 
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
+        // Raw motion is used instead of cursor position, so looking around is not limited by
+        // the edges of the window
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+            self.fly_controller.add_mouse_delta(dx as FloatType, dy as FloatType);
+        }
+    }
+
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
         self.window.as_ref().unwrap().request_redraw();
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math_utils::vector::Vector3;
+    use crate::object::{ObjectPath, RayHit};
+
+    #[test]
+    fn test_invalid_view_height_is_rejected() {
+        let parse = |view_height: &str| Arguments::try_parse_from([
+            "zesty_engine2", "scene.json", "--render-type", "face", &format!("--view-height={view_height}")
+        ]);
+
+        assert_eq!(parse("2.5").unwrap().view_height, Some(2.5));
+        assert!(parse("0").is_err());
+        assert!(parse("-3").is_err());
+        assert!(parse("abc").is_err());
+    }
+
+    #[test]
+    fn test_pick_title() {
+        let pick = |path: ObjectPath, position| PickHit {
+            path,
+            hit: RayHit {
+                face_index: 2,
+                barycentric: Vector3::new([1.0, 0.0, 0.0]),
+                position,
+                distance: 1.0
+            }
+        };
+        let first = pick(vec![], Vector3::new([0.0, 0.0, 1.0]));
+        let second = pick(vec![1, 0], Vector3::new([3.0, 4.0, 1.0]));
+
+        assert_eq!(pick_title(None, Some(&first)), "zesty_engine2 - nothing picked");
+        assert_eq!(pick_title(Some(&first), None), "zesty_engine2 - object [], face 2 at (0.000, 0.000, 1.000)");
+        assert_eq!(
+            pick_title(Some(&second), Some(&first)),
+            "zesty_engine2 - object [1, 0], face 2 at (3.000, 4.000, 1.000), 5.000 from the previous pick"
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use winit::event_loop::{ControlFlow, EventLoop};
 use clap::Parser;
 
-use zesty_engine2::{App, Arguments};
+use zesty_engine2::{App, Arguments, run_headless};
 
 
 fn main() {
     let args = Arguments::parse();
+    if args.is_headless() {
+        run_headless(args).unwrap();
+        return;
+    }
+
     let mut app = App::new(args);
 
     let event_loop = EventLoop::new().unwrap();
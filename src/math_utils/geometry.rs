@@ -0,0 +1,484 @@
+use super::{FloatType, transform_3d_point};
+use super::matrix::Matrix4;
+use super::vector::Vector3;
+
+
+// Every point p on the plane satisfies normal . p + distance = 0.
+// Points on the side the normal points to have a positive signed distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<FloatType>,
+    pub distance: FloatType
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<FloatType>, distance: FloatType) -> Self {
+        Self { normal, distance }
+    }
+
+    // Builds the plane a*x + b*y + c*z + d = 0, scaled so that the normal has unit length.
+    // A zero normal is kept as is: such a plane has every point on its positive side (if d > 0),
+    // which is what an infinitely far frustum plane turns into.
+    pub fn from_coefficients(a: FloatType, b: FloatType, c: FloatType, d: FloatType) -> Self {
+        let normal = Vector3::new([a, b, c]);
+        let length = normal.length();
+        if length == 0.0 {
+            return Self::new(normal, d);
+        }
+
+        Self::new(normal.normalized(), d / length)
+    }
+
+    pub fn signed_distance(&self, point: Vector3<FloatType>) -> FloatType {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vector3<FloatType>,
+    pub radius: FloatType
+}
+
+impl Sphere {
+    pub fn new(center: Vector3<FloatType>, radius: FloatType) -> Self {
+        Self { center, radius }
+    }
+
+    // The result is conservative: with non-uniform scale, the sphere is scaled by the largest
+    // axis, so it still contains everything the original sphere did
+    pub fn transformed(&self, matrix: &Matrix4) -> Self {
+        let max_scale = (0..3)
+            .map(|col| Vector3::new([matrix[0][col], matrix[1][col], matrix[2][col]]).length())
+            .fold(0.0, FloatType::max);
+
+        Self::new(transform_3d_point(matrix, self.center), self.radius * max_scale)
+    }
+
+    // Whether the whole sphere is on the negative side of `plane`
+    pub fn is_behind_plane(&self, plane: &Plane) -> bool {
+        plane.signed_distance(self.center) < -self.radius
+    }
+
+    pub fn contains_point(&self, point: Vector3<FloatType>) -> bool {
+        (point - self.center).sq_length() <= self.radius * self.radius
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub a: Vector3<FloatType>,
+    pub b: Vector3<FloatType>,
+    pub c: Vector3<FloatType>
+}
+
+impl Triangle {
+    pub fn new(a: Vector3<FloatType>, b: Vector3<FloatType>, c: Vector3<FloatType>) -> Self {
+        Self { a, b, c }
+    }
+
+    // Unit normal, facing the side from which the corners go counter-clockwise.
+    // Degenerate triangles have a zero normal.
+    pub fn normal(&self) -> Vector3<FloatType> {
+        (self.b - self.a).cross(self.c - self.a).normalized()
+    }
+
+    pub fn area(&self) -> FloatType {
+        (self.b - self.a).cross(self.c - self.a).length() * 0.5
+    }
+
+    // Point with the given weights for a, b and c, e.g. from `TriangleHit::barycentric`
+    pub fn point_from_barycentric(&self, barycentric: Vector3<FloatType>) -> Vector3<FloatType> {
+        self.a * barycentric.x() + self.b * barycentric.y() + self.c * barycentric.z()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<FloatType>,
+    // Not required to be normalized, but distances are only meaningful when it is
+    pub direction: Vector3<FloatType>
+}
+
+// Result of a ray-triangle intersection.
+// `distance` is in multiples of the ray's direction, and `barycentric` holds the weights of
+// the triangle's 3 corners at the hit point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub distance: FloatType,
+    pub barycentric: Vector3<FloatType>
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<FloatType>, direction: Vector3<FloatType>) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn point_at(&self, distance: FloatType) -> Vector3<FloatType> {
+        self.origin + self.direction * distance
+    }
+
+    // Moller-Trumbore intersection. Both sides of the triangle can be hit, and hits behind the
+    // origin are ignored. See:
+    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    pub fn intersect_triangle(&self, triangle: &Triangle) -> Option<TriangleHit> {
+        let Triangle { a, b, c } = *triangle;
+        let edge_1 = b - a;
+        let edge_2 = c - a;
+        let p = self.direction.cross(edge_2);
+        let determinant = edge_1.dot(p);
+
+        // The ray is parallel to the triangle. The determinant grows with the length of the
+        // direction and of both edges, so the tolerance has to as well: otherwise tiny triangles
+        // would always look parallel, and huge ones never would.
+        let scale = self.direction.length() * edge_1.length() * edge_2.length();
+        if determinant.abs() <= FloatType::EPSILON * scale {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let origin_offset = self.origin - a;
+        let u = origin_offset.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = origin_offset.cross(edge_1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge_2.dot(q) * inverse_determinant;
+        if distance < 0.0 {
+            return None;
+        }
+
+        Some(TriangleHit {
+            distance,
+            barycentric: Vector3::new([1.0 - u - v, u, v])
+        })
+    }
+
+    // Distance to the first point of the sphere along the ray, or 0 if the ray starts inside it.
+    // Solves |origin + t * direction - center|^2 = radius^2 for t.
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<FloatType> {
+        let offset = self.origin - sphere.center;
+        let c = offset.sq_length() - sphere.radius * sphere.radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+
+        // With b halved, which cancels out the 2s of the quadratic formula
+        let a = self.direction.sq_length();
+        let half_b = self.direction.dot(offset);
+        let discriminant = half_b * half_b - a * c;
+        if a == 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        // The origin is outside, so both hits are on the same side of it
+        let distance = (-half_b - discriminant.sqrt()) / a;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    // Distance to the first point of the box along the ray, or 0 if the ray starts inside it.
+    // Slab method: the ray is inside the box where it is between the 2 planes of every axis. See:
+    // https://tavianator.com/2011/ray_box.html
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<FloatType> {
+        let mut enter: FloatType = 0.0;
+        let mut exit = FloatType::INFINITY;
+
+        for i in 0..3 {
+            if self.direction[i] == 0.0 {
+                // Parallel to the slab, so it never enters it if it is not already in
+                if self.origin[i] < aabb.min[i] || self.origin[i] > aabb.max[i] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inverse_direction = 1.0 / self.direction[i];
+            let t1 = (aabb.min[i] - self.origin[i]) * inverse_direction;
+            let t2 = (aabb.max[i] - self.origin[i]) * inverse_direction;
+            enter = enter.max(t1.min(t2));
+            exit = exit.min(t1.max(t2));
+        }
+
+        (enter <= exit).then_some(enter)
+    }
+}
+
+// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<FloatType>,
+    pub max: Vector3<FloatType>
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<FloatType>, max: Vector3<FloatType>) -> Self {
+        Self { min, max }
+    }
+
+    // Returns `None` if there are no points
+    pub fn from_points(points: &[Vector3<FloatType>]) -> Option<Self> {
+        let first = *points.first()?;
+        let mut aabb = Self::new(first, first);
+
+        for point in &points[1..] {
+            for i in 0..3 {
+                aabb.min[i] = aabb.min[i].min(point[i]);
+                aabb.max[i] = aabb.max[i].max(point[i]);
+            }
+        }
+
+        Some(aabb)
+    }
+
+    pub fn center(&self) -> Vector3<FloatType> {
+        Vector3::new([
+            (self.min.x() + self.max.x()) * 0.5,
+            (self.min.y() + self.max.y()) * 0.5,
+            (self.min.z() + self.max.z()) * 0.5
+        ])
+    }
+
+    pub fn contains_point(&self, point: Vector3<FloatType>) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+
+    // The corner furthest along `direction`
+    fn support_point(&self, direction: Vector3<FloatType>) -> Vector3<FloatType> {
+        let mut point = self.min;
+        for i in 0..3 {
+            if direction[i] >= 0.0 {
+                point[i] = self.max[i];
+            }
+        }
+        point
+    }
+
+    // Whether the whole box is on the negative side of `plane`. Only the corner furthest along
+    // the normal needs to be checked.
+    pub fn is_behind_plane(&self, plane: &Plane) -> bool {
+        plane.signed_distance(self.support_point(plane.normal)) < 0.0
+    }
+
+    // Whether the plane passes through the box, i.e. it has corners on both sides
+    pub fn intersects_plane(&self, plane: &Plane) -> bool {
+        !self.is_behind_plane(plane)
+            && plane.signed_distance(self.support_point(-plane.normal)) <= 0.0
+    }
+}
+
+// Convex volume bounded by planes with normals pointing inwards, such as the one from
+// `Camera::frustum`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6]
+}
+
+impl Frustum {
+    pub fn new(planes: [Plane; 6]) -> Self {
+        Self { planes }
+    }
+
+    pub fn contains_point(&self, point: Vector3<FloatType>) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    // Conservative: false only if the sphere is entirely outside of one of the planes. A sphere
+    // near a corner of the frustum can still pass without touching it.
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        !self.planes.iter().any(|plane| sphere.is_behind_plane(plane))
+    }
+
+    // Conservative in the same way as `intersects_sphere`
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        !self.planes.iter().any(|plane| aabb.is_behind_plane(plane))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::ApproxEq;
+    use super::super::vector::{vector, Vector};
+    use super::super::vector::tests::approx_cmp_vector;
+
+    #[test]
+    fn test_plane_from_coefficients() {
+        let plane = Plane::from_coefficients(0.0, 2.0, 0.0, -4.0);
+
+        approx_cmp_vector(plane.normal, vector![0.0, 1.0, 0.0]);
+        assert_eq!(plane.distance, -2.0);
+        assert_eq!(plane.signed_distance(vector![5.0, 3.0, -1.0]), 1.0);
+    }
+
+    #[test]
+    fn test_sphere_behind_plane() {
+        let plane = Plane::new(vector![1.0, 0.0, 0.0], 0.0);
+
+        assert!(Sphere::new(vector![-2.0, 0.0, 0.0], 1.0).is_behind_plane(&plane));
+        // Touching the plane counts as in front of it
+        assert!(!Sphere::new(vector![-1.0, 0.0, 0.0], 1.0).is_behind_plane(&plane));
+    }
+
+    #[test]
+    fn test_transform_sphere() {
+        let matrix = Matrix4::new([
+            [2.0, 0.0, 0.0, 1.0],
+            [0.0, 3.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        let sphere = Sphere::new(vector![1.0, 1.0, 1.0], 2.0).transformed(&matrix);
+
+        approx_cmp_vector(sphere.center, vector![3.0, 3.0, 1.0]);
+        assert_eq!(sphere.radius, 6.0);
+    }
+
+    #[test]
+    fn test_ray_triangle_hit() {
+        let ray = Ray::new(vector![0.25, 0.25, -1.0], vector![0.0, 0.0, 1.0]);
+        let hit = ray.intersect_triangle(&Triangle::new(
+            vector![0.0, 0.0, 2.0],
+            vector![1.0, 0.0, 2.0],
+            vector![0.0, 1.0, 2.0]
+        )).unwrap();
+
+        assert_eq!(hit.distance, 3.0);
+        approx_cmp_vector(hit.barycentric, vector![0.5, 0.25, 0.25]);
+        approx_cmp_vector(ray.point_at(hit.distance), vector![0.25, 0.25, 2.0]);
+    }
+
+    #[test]
+    fn test_ray_triangle_hit_tiny_triangle() {
+        // A millimetre-sized triangle, hit head-on by a short ray
+        let triangle = Triangle::new(
+            vector![0.0, 0.0, 0.002],
+            vector![0.001, 0.0, 0.002],
+            vector![0.0, 0.001, 0.002]
+        );
+        let ray = Ray::new(vector![0.00025, 0.00025, 0.0], vector![0.0, 0.0, 0.01]);
+        let hit = ray.intersect_triangle(&triangle).unwrap();
+
+        assert!(hit.distance.approx_eq(0.2, (1e-6, 4)));
+        assert!(hit.barycentric.approx_eq(vector![0.5, 0.25, 0.25], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_ray_triangle_miss() {
+        let triangle = Triangle::new(vector![0.0, 0.0, 2.0], vector![1.0, 0.0, 2.0], vector![0.0, 1.0, 2.0]);
+
+        // Outside of the triangle, behind the ray, and parallel to the triangle
+        assert_eq!(Ray::new(vector![0.75, 0.75, 0.0], vector![0.0, 0.0, 1.0]).intersect_triangle(&triangle), None);
+        assert_eq!(Ray::new(vector![0.25, 0.25, 3.0], vector![0.0, 0.0, 1.0]).intersect_triangle(&triangle), None);
+        assert_eq!(Ray::new(vector![0.25, 0.25, 0.0], vector![1.0, 0.0, 0.0]).intersect_triangle(&triangle), None);
+
+        // Parallel to a huge triangle, up to rounding
+        let huge = Triangle::new(vector![0.0, 0.0, 0.0], vector![1e4, 0.0, 0.0], vector![0.0, 1e4, 0.0]);
+        let ray = Ray::new(vector![1.0, 1.0, -1e-6], vector![1.0, 0.0, FloatType::EPSILON * 0.5]);
+        assert_eq!(ray.intersect_triangle(&huge), None);
+    }
+
+    #[test]
+    fn test_aabb_from_points() {
+        let aabb = Aabb::from_points(&[
+            vector![1.0, -2.0, 3.0],
+            vector![-1.0, 5.0, 0.0],
+            vector![0.0, 0.0, 4.0]
+        ]).unwrap();
+
+        assert_eq!(aabb, Aabb::new(vector![-1.0, -2.0, 0.0], vector![1.0, 5.0, 4.0]));
+        approx_cmp_vector(aabb.center(), vector![0.0, 1.5, 2.0]);
+        assert_eq!(Aabb::from_points(&[]), None);
+    }
+
+    #[test]
+    fn test_triangle() {
+        let triangle = Triangle::new(vector![0.0, 0.0, 0.0], vector![2.0, 0.0, 0.0], vector![0.0, 2.0, 0.0]);
+
+        approx_cmp_vector(triangle.normal(), vector![0.0, 0.0, 1.0]);
+        assert_eq!(triangle.area(), 2.0);
+        approx_cmp_vector(triangle.point_from_barycentric(vector![0.5, 0.25, 0.25]), vector![0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_ray_sphere() {
+        let sphere = Sphere::new(vector![0.0, 0.0, 5.0], 1.0);
+
+        let ray = Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        assert_eq!(ray.intersect_sphere(&sphere), Some(4.0));
+        // Distances are in multiples of the direction
+        let ray = Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 2.0]);
+        assert_eq!(ray.intersect_sphere(&sphere), Some(2.0));
+
+        // Starting inside, pointing away, and passing by
+        assert_eq!(Ray::new(vector![0.0, 0.5, 5.0], vector![1.0, 0.0, 0.0]).intersect_sphere(&sphere), Some(0.0));
+        assert_eq!(Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, -1.0]).intersect_sphere(&sphere), None);
+        assert_eq!(Ray::new(vector![0.0, 2.0, 0.0], vector![0.0, 0.0, 1.0]).intersect_sphere(&sphere), None);
+    }
+
+    #[test]
+    fn test_ray_aabb() {
+        let aabb = Aabb::new(vector![-1.0, -1.0, 2.0], vector![1.0, 1.0, 4.0]);
+
+        let ray = Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        assert_eq!(ray.intersect_aabb(&aabb), Some(2.0));
+        let diagonal = Ray::new(vector![-3.0, -3.0, 0.0], vector![1.0, 1.0, 1.0]);
+        assert_eq!(diagonal.intersect_aabb(&aabb), Some(2.0));
+
+        // Starting inside, pointing away, passing by, and parallel to a slab it is outside of
+        assert_eq!(Ray::new(vector![0.0, 0.0, 3.0], vector![1.0, 0.0, 0.0]).intersect_aabb(&aabb), Some(0.0));
+        assert_eq!(Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, -1.0]).intersect_aabb(&aabb), None);
+        assert_eq!(Ray::new(vector![0.0, 0.0, 0.0], vector![1.0, 0.0, 1.0]).intersect_aabb(&aabb), None);
+        assert_eq!(Ray::new(vector![0.0, 2.0, 0.0], vector![0.0, 0.0, 1.0]).intersect_aabb(&aabb), None);
+    }
+
+    #[test]
+    fn test_aabb_plane() {
+        let aabb = Aabb::new(vector![-1.0, -1.0, -1.0], vector![1.0, 1.0, 1.0]);
+        assert!(aabb.contains_point(vector![1.0, 0.0, -0.5]));
+        assert!(!aabb.contains_point(vector![1.5, 0.0, 0.0]));
+
+        // A diagonal plane that only the corner at (1, 1, 1) reaches past
+        let plane = Plane::from_coefficients(1.0, 1.0, 1.0, -2.5);
+        assert!(!aabb.is_behind_plane(&plane));
+        assert!(aabb.intersects_plane(&plane));
+
+        let plane = Plane::from_coefficients(1.0, 1.0, 1.0, -3.5);
+        assert!(aabb.is_behind_plane(&plane));
+        assert!(!aabb.intersects_plane(&plane));
+
+        // Entirely in front
+        let plane = Plane::from_coefficients(0.0, 1.0, 0.0, 2.0);
+        assert!(!aabb.is_behind_plane(&plane));
+        assert!(!aabb.intersects_plane(&plane));
+    }
+
+    #[test]
+    fn test_frustum() {
+        // The box from -1 to 1 on every axis
+        let frustum = Frustum::new([
+            Plane::new(vector![1.0, 0.0, 0.0], 1.0),
+            Plane::new(vector![-1.0, 0.0, 0.0], 1.0),
+            Plane::new(vector![0.0, 1.0, 0.0], 1.0),
+            Plane::new(vector![0.0, -1.0, 0.0], 1.0),
+            Plane::new(vector![0.0, 0.0, 1.0], 1.0),
+            Plane::new(vector![0.0, 0.0, -1.0], 1.0)
+        ]);
+
+        assert!(frustum.contains_point(vector![0.5, -1.0, 0.0]));
+        assert!(!frustum.contains_point(vector![0.0, 0.0, 1.5]));
+
+        assert!(frustum.intersects_sphere(&Sphere::new(vector![0.0, 0.0, 0.0], 0.5)));
+        assert!(frustum.intersects_sphere(&Sphere::new(vector![1.5, 0.0, 0.0], 1.0)));
+        assert!(!frustum.intersects_sphere(&Sphere::new(vector![3.0, 0.0, 0.0], 1.0)));
+
+        assert!(frustum.intersects_aabb(&Aabb::new(vector![0.5, 0.5, 0.5], vector![2.0, 2.0, 2.0])));
+        assert!(!frustum.intersects_aabb(&Aabb::new(vector![1.5, -1.0, -1.0], vector![2.0, 1.0, 1.0])));
+    }
+}
@@ -1,11 +1,19 @@
 use std::fmt::Debug;
-use std::ops::Index;
-use std::ops::IndexMut;
+use std::ops::{Add, Index, IndexMut, Mul, Sub};
 use std::default::Default;
 
 use float_cmp::ApproxEq;
 
-use super::{FloatType, vector::Vector};
+use super::{FloatType, MathError};
+use super::vector::{Vector, Vector3};
+use super::quaternion::Quaternion;
+
+// A pivot smaller than this makes the matrix singular. Pivots are taken once every row and column
+// has been scaled so that its largest entry is around 1, see `Matrix::invert`.
+const SINGULAR_TOLERANCE: FloatType = 1e-6;
+
+// How far the axes of a matrix may be from perpendicular before it counts as sheared
+const SHEAR_TOLERANCE: FloatType = 1e-4;
 
 #[derive(Clone, PartialEq)]
 pub struct Matrix<const N: usize>([[FloatType; N]; N]);
@@ -20,8 +28,8 @@ impl<const N: usize> Matrix<N> {
 
     pub fn identity_matrix() -> Self {
         let mut matrix = [[0.0; N]; N];
-        for i in 0..N {
-            matrix[i][i] = 1.0;
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[i] = 1.0;
         }
 
         Self(matrix)
@@ -29,21 +37,123 @@ impl<const N: usize> Matrix<N> {
 
     pub const SIZE: usize = N;
 
-    pub fn invert(&self, is_homogenous: bool) -> Result<Matrix<N>, String> {
-        let total_row: usize = if is_homogenous { N-1 } else { N };
-        let total_column: usize = N;
+    pub fn from_rows(rows: [Vector<N, FloatType>; N]) -> Self {
+        Self(rows.map(|row| *row.array()))
+    }
+
+    pub fn from_cols(cols: [Vector<N, FloatType>; N]) -> Self {
+        Self::from_rows(cols).transpose()
+    }
+
+    pub fn row(&self, index: usize) -> Vector<N, FloatType> {
+        Vector::new(self.0[index])
+    }
+
+    pub fn col(&self, index: usize) -> Vector<N, FloatType> {
+        Vector::new(self.0.map(|row| row[index]))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut output = [[0.0; N]; N];
+        for (i, row) in output.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self[j][i];
+            }
+        }
+
+        Self(output)
+    }
+
+    pub fn trace(&self) -> FloatType {
+        (0..N).map(|i| self[i][i]).sum()
+    }
+
+    // Found by reducing the matrix to upper triangular form, where the determinant is the
+    // product of the diagonal. Every row swap flips its sign.
+    pub fn determinant(&self) -> FloatType {
+        let mut matrix = self.clone();
+        let mut determinant = 1.0;
+
+        for col in 0..N {
+            let pivot = (col..N)
+                .max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))
+                .unwrap_or(col);
+            let pivot_val = matrix[pivot][col];
+            if pivot_val == 0.0 {
+                return 0.0;
+            }
+
+            if pivot != col {
+                swap_row(&mut matrix, pivot, col);
+                determinant = -determinant;
+            }
+            determinant *= pivot_val;
 
-        if *self == Self::identity_matrix() {
-            return Ok(self.clone());
+            for row in (col + 1)..N {
+                let multiplier = -matrix[row][col] / pivot_val;
+                add_multiply_row(&mut matrix, row, col, multiplier);
+            }
         }
 
+        determinant
+    }
+
+    // Gauss-Jordan elimination with partial pivoting: each column is pivoted on its largest
+    // remaining entry, which keeps rounding errors small. Matrices whose pivot gets too small
+    // compared to their largest entry are treated as singular.
+    // See: https://en.wikipedia.org/wiki/Gaussian_elimination#Pivoting
+    // Columns, then rows, are scaled before the elimination so that the singularity check does not
+    // depend on units: a large translation next to a small scale is still a well-behaved matrix.
+    // With the scaled matrix B = R * self * C (R and C diagonal), the inverse is C * B^-1 * R.
+    pub fn invert(&self) -> Result<Matrix<N>, MathError> {
         let mut matrix = self.clone();
+
+        let mut col_scales = [1.0; N];
+        for (col, col_scale) in col_scales.iter_mut().enumerate() {
+            let largest_entry = (0..N).fold(0.0, |max: FloatType, row| max.max(matrix[row][col].abs()));
+            *col_scale = equilibration_scale(largest_entry)?;
+
+            for row in 0..N {
+                matrix[row][col] *= *col_scale;
+            }
+        }
+
         let mut inv_matrix = Matrix::<N>::identity_matrix();
+        for row in 0..N {
+            let largest_entry = matrix[row].iter().fold(0.0, |max: FloatType, x| max.max(x.abs()));
+            let row_scale = equilibration_scale(largest_entry)?;
+
+            scale_row(&mut matrix, row, row_scale);
+            scale_row(&mut inv_matrix, row, row_scale);
+        }
+
+        for col in 0..N {
+            let pivot = (col..N)
+                .max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))
+                .unwrap_or(col);
+            let pivot_val = matrix[pivot][col];
+
+            if pivot_val.is_nan() || pivot_val.abs() <= SINGULAR_TOLERANCE {
+                return Err(MathError::SingularMatrix);
+            }
 
-        ensure_pivot_non_zero(&mut matrix, &mut inv_matrix, total_row, total_column)?;
-        forward_substitution(&mut matrix, &mut inv_matrix, total_row, total_column);
-        scale_pivot_to_one(&mut matrix, &mut inv_matrix, total_row, total_column);
-        backward_substitution(&mut matrix, &mut inv_matrix, total_row, total_column);
+            swap_row(&mut matrix, pivot, col);
+            swap_row(&mut inv_matrix, pivot, col);
+
+            scale_row(&mut matrix, col, 1.0 / pivot_val);
+            scale_row(&mut inv_matrix, col, 1.0 / pivot_val);
+
+            // Clear the rest of the column, above and below the pivot
+            for row in (0..N).filter(|row| *row != col) {
+                let multiplier = -matrix[row][col];
+                add_multiply_row(&mut matrix, row, col, multiplier);
+                add_multiply_row(&mut inv_matrix, row, col, multiplier);
+            }
+        }
+
+        for (row, col_scale) in col_scales.into_iter().enumerate() {
+            scale_row(&mut inv_matrix, row, col_scale);
+        }
 
         Ok(inv_matrix)
     }
@@ -64,6 +174,214 @@ impl<const N: usize> Matrix<N> {
     }
 }
 
+// The parts of an affine matrix, which is equal to translation * rotation * scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecomposedMatrix {
+    pub translation: Vector3<FloatType>,
+    pub rotation: Quaternion,
+    pub scale: Vector3<FloatType>
+}
+
+impl Matrix3 {
+    // Rotation whose columns are the right, down and forward axes of something facing `forward`,
+    // which is how cameras are oriented. `None` when `forward` is zero or parallel to `up`.
+    pub fn look_at_rotation(forward: Vector3<FloatType>, up: Vector3<FloatType>) -> Option<Self> {
+        let forward = forward.normalized();
+        let right = forward.cross(up).normalized();
+        if forward.length() == 0.0 || right.length() == 0.0 {
+            return None;
+        }
+
+        // +y points downwards
+        let down = forward.cross(right);
+        Some(Self::from_cols([right, down, forward]))
+    }
+}
+
+impl Matrix4 {
+    pub fn from_translation(translation: Vector3<FloatType>) -> Self {
+        let mut matrix = Self::identity_matrix();
+        for i in 0..3 {
+            matrix[i][3] = translation[i];
+        }
+        matrix
+    }
+
+    pub fn from_rotation(rotation: Quaternion) -> Self {
+        let mut matrix = Self::identity_matrix();
+        rotation.edit_3d_matrix(&mut matrix, Vector3::one());
+        matrix
+    }
+
+    pub fn from_scale(scale: Vector3<FloatType>) -> Self {
+        let mut matrix = Self::identity_matrix();
+        for i in 0..3 {
+            matrix[i][i] = scale[i];
+        }
+        matrix
+    }
+
+    // Same as from_translation * from_rotation * from_scale, without the multiplications
+    pub fn from_trs(translation: Vector3<FloatType>, rotation: Quaternion, scale: Vector3<FloatType>) -> Self {
+        let mut matrix = Self::from_translation(translation);
+        rotation.edit_3d_matrix(&mut matrix, scale);
+        matrix
+    }
+
+    // View matrix of a camera at `eye` facing `target`, mapping world space into camera space
+    // (+x right, +y down, +z forward). `up` is the world direction that should point towards the
+    // top of the screen.
+    // Returns `None` when the orientation is ambiguous: `target` is at `eye`, or `up` is
+    // parallel to the viewing direction.
+    pub fn look_at(eye: Vector3<FloatType>, target: Vector3<FloatType>, up: Vector3<FloatType>) -> Option<Self> {
+        let rotation = Matrix3::look_at_rotation(target - eye, up)?;
+
+        // Inverse of translation(eye) * rotation, with the rotation transposed
+        let inverse_rotation = rotation.transpose();
+        let inverse_eye = inverse_rotation.multiply_vector(eye);
+        let mut matrix = Self::identity_matrix();
+        for row in 0..3 {
+            for col in 0..3 {
+                matrix[row][col] = inverse_rotation[row][col];
+            }
+            matrix[row][3] = -inverse_eye[row];
+        }
+
+        Some(matrix)
+    }
+
+    // Splits an affine matrix back into translation, rotation and scale.
+    // Scale always comes out positive. Negating the scale of two axes is the same as a half turn,
+    // so such matrices decompose into that rotation instead; an odd number of negated axes mirrors
+    // space and is rejected.
+    pub fn decompose(&self) -> Result<DecomposedMatrix, MathError> {
+        if self[3] != [0.0, 0.0, 0.0, 1.0] {
+            return Err(MathError::NotAffine);
+        }
+
+        let translation = Vector3::new([self[0][3], self[1][3], self[2][3]]);
+
+        // Each column of the upper-left 3x3 part is a rotated axis, stretched by its scale
+        let mut axes = [Vector3::<FloatType>::default(); 3];
+        let mut scale = Vector3::<FloatType>::default();
+        for (col, axis) in axes.iter_mut().enumerate() {
+            let column = Vector3::new([self[0][col], self[1][col], self[2][col]]);
+            scale[col] = column.length();
+            if scale[col] <= FloatType::EPSILON {
+                return Err(MathError::ZeroScale(col));
+            }
+            *axis = column.normalized();
+        }
+
+        let [x_axis, y_axis, z_axis] = axes;
+        let is_sheared = [(x_axis, y_axis), (x_axis, z_axis), (y_axis, z_axis)].iter()
+            .any(|(a, b)| a.dot(*b).abs() > SHEAR_TOLERANCE);
+        if is_sheared {
+            return Err(MathError::Shear);
+        }
+
+        // A right-handed set of axes has a positive triple product (the determinant)
+        if x_axis.cross(y_axis).dot(z_axis) < 0.0 {
+            return Err(MathError::NegativeScale);
+        }
+
+        let rotation = Matrix3::from_cols(axes);
+
+        Ok(DecomposedMatrix {
+            translation,
+            rotation: Quaternion::from_rotation_matrix(&rotation),
+            scale
+        })
+    }
+}
+
+impl<const N: usize> Mul for &Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut output = [[0.0; N]; N];
+        for (i, row) in output.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                for k in 0..N {
+                    *value += self[i][k] * rhs[k][j];
+                }
+            }
+        }
+
+        Matrix(output)
+    }
+}
+
+impl<const N: usize> Mul for Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl<const N: usize> Mul<Vector<N, FloatType>> for &Matrix<N> {
+    type Output = Vector<N, FloatType>;
+
+    fn mul(self, rhs: Vector<N, FloatType>) -> Self::Output {
+        self.multiply_vector(rhs)
+    }
+}
+
+impl<const N: usize> Mul<Vector<N, FloatType>> for Matrix<N> {
+    type Output = Vector<N, FloatType>;
+
+    fn mul(self, rhs: Vector<N, FloatType>) -> Self::Output {
+        self.multiply_vector(rhs)
+    }
+}
+
+impl<const N: usize> Add for &Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut output = self.clone();
+        for i in 0..N {
+            for j in 0..N {
+                output[i][j] += rhs[i][j];
+            }
+        }
+
+        output
+    }
+}
+
+impl<const N: usize> Add for Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl<const N: usize> Sub for &Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut output = self.clone();
+        for i in 0..N {
+            for j in 0..N {
+                output[i][j] -= rhs[i][j];
+            }
+        }
+
+        output
+    }
+}
+
+impl<const N: usize> Sub for Matrix<N> {
+    type Output = Matrix<N>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        &self - &rhs
+    }
+}
+
 impl<const N: usize> Default for Matrix<N> {
     fn default() -> Self {
         Self::identity_matrix()
@@ -111,84 +429,14 @@ impl<const N: usize> Debug for Matrix<N> {
     }
 }
 
-fn ensure_pivot_non_zero<const N: usize>(
-    matrix: &mut Matrix<N>, inv_matrix: &mut Matrix<N>,
-    total_row: usize, total_column: usize
-) -> Result<(), String> {
-    for col in 0..total_column {
-        // Making sure pivot is a non-zero number
-        // If zero, swap row with one that has the biggest absolute value
-        let mut pivot = col;
-        let mut pivot_val = matrix[col][col];
-
-        // It should be noted that singular matrix is uncommon in the context of transformation.
-        // The following implementation is subpar; there's a chance for singular matrix to escape
-        // this function.
-        if pivot_val != 0.0 {
-            continue
-        }
-
-        for row in 0..total_row {
-            if matrix[row][col].abs() > pivot_val.abs() {
-                pivot = row;
-                pivot_val = matrix[row][col];
-            }
-        }
-
-        if pivot_val == 0.0 {
-            return Err("Matrix has no inverse".to_string())
-        }
-
-        swap_row(matrix, pivot, col);
-        swap_row(inv_matrix, pivot, col);
-    }
-    Ok(())
-}
-
-fn forward_substitution<const N: usize>(
-    matrix: &mut Matrix<N>, inv_matrix: &mut Matrix<N>,
-    total_row: usize, total_column: usize
-) -> () {
-    // Make every value under pivot points zero
-    for col in 0..total_column {
-        for row in (col+1)..total_row {
-            let multiplier = -(matrix[row][col] / matrix[col][col]);
-            // Multiply pivot point's row by multiplier, and add to current row
-            add_multiply_row(matrix, row, col, multiplier);
-            add_multiply_row(inv_matrix, row, col, multiplier);
-            matrix[row][col] = 0.0;
-        }
-    }
-}
-
-fn scale_pivot_to_one<const N: usize>(
-    matrix: &mut Matrix<N>, inv_matrix: &mut Matrix<N>,
-    _total_row: usize, total_column: usize
-) -> () {
-    // Divide each row to turn the pivot into 1
-    for col in 0..total_column {
-        // Fancy name for 'divisor'
-        let inverse_multiplier = 1.0/matrix[col][col];
-        scale_row(matrix, col, inverse_multiplier);
-        scale_row(inv_matrix, col, inverse_multiplier);
-        matrix[col][col] = 1.0;
+// Power of 2 that brings `largest_entry` close to 1, so that scaling by it is exact.
+// A row or column that is entirely 0 makes the matrix singular.
+fn equilibration_scale(largest_entry: FloatType) -> Result<FloatType, MathError> {
+    if largest_entry == 0.0 || !largest_entry.is_finite() {
+        return Err(MathError::SingularMatrix);
     }
-}
 
-fn backward_substitution<const N: usize>(
-    matrix: &mut Matrix<N>, inv_matrix: &mut Matrix<N>,
-    total_row: usize, total_column: usize
-) -> () {
-    // Backward substitution
-    for row in 0..total_row {
-        for col in (row+1)..total_column {
-            let multiplier = -matrix[row][col];
-            add_multiply_row(matrix, row, col, multiplier);
-            add_multiply_row(inv_matrix, row, col, multiplier);
-
-            matrix[row][col] = 0.0;
-        }
-    }
+    Ok((2.0 as FloatType).powi(-(largest_entry.log2().round() as i32)))
 }
 
 // 3 functions that perform the elementary row operations needed for inverting a matrix
@@ -197,8 +445,8 @@ fn swap_row<const N: usize>(
     matrix: &mut Matrix<N>,
     row_index_1: usize,
     row_index_2: usize
-) -> () {
-    let tmp = matrix[row_index_1].clone();
+) {
+    let tmp = matrix[row_index_1];
     matrix[row_index_1] = matrix[row_index_2];
     matrix[row_index_2] = tmp;
 }
@@ -209,7 +457,7 @@ fn add_multiply_row<const N: usize>(
     target_row_index: usize,
     add_row_index: usize,
     multiplier: FloatType,
-) -> () {
+) {
     let columns = Matrix::<N>::SIZE;
 
     for col in 0..columns {
@@ -222,7 +470,7 @@ fn scale_row<const N: usize>(
     matrix: &mut Matrix<N>,
     target_row_index: usize,
     multiplier: FloatType
-) -> () {
+) {
     let columns = Matrix::<N>::SIZE;
 
     for col in 0..columns {
@@ -234,7 +482,10 @@ fn scale_row<const N: usize>(
 #[cfg(test)]
 pub mod tests {
     use float_cmp::approx_eq;
+    use super::super::consts::FRAC_PI_2;
+
     use super::super::vector::vector;
+    use super::super::vector::tests::approx_cmp_vector;
     use super::*;
 
     fn approx_cmp_matrix<const N: usize>(a: Matrix<N>, b: Matrix<N>) {
@@ -242,116 +493,93 @@ pub mod tests {
     }
 
     #[test]
-    fn test_ensure_pivot_non_zero() {
-        let mut matrix = Matrix([
-            [1.00, 2.00, 9.00],
-            [3.00, 0.00, 8.00],
-            [6.00, 3.00, 5.00]
+    fn test_inverting_matrix() {
+        let matrix = Matrix([
+            [1.00, -3.00, 3.01],
+            [4.50, 5.00, 7.00],
+            [3.00, 97.00, 8.00]
         ]);
-        let mut dummy_inv_matrix = Matrix::<3>::identity_matrix();
-        let total_row = 3;
-        let total_column = 3;
-        let result = ensure_pivot_non_zero(&mut matrix, &mut dummy_inv_matrix, total_row, total_column);
-
-        assert_eq!(result, Ok(()));
-        approx_cmp_matrix(matrix, Matrix([
-            [1.00, 2.00, 9.00],
-            [6.00, 3.00, 5.00],
-            [3.00, 0.00, 8.00]
-        ]));
-        approx_cmp_matrix(dummy_inv_matrix, Matrix([
-            [1.00, 0.00, 0.00],
-            [0.00, 0.00, 1.00],
-            [0.00, 1.00, 0.00]
+
+        let inv_matrix = matrix.invert().unwrap();
+        approx_cmp_matrix(inv_matrix, Matrix([
+            [-42600.00/44981.00, 63194.00/134943.00, -7210.00/134943.00],
+            [-1000.00/44981.00, -206.00/134943.00, 1309.00/134943.00],
+            [28100.00/44981.00, -21200.00/134943.00, 3700.00/134943.00]
         ]));
     }
 
     #[test]
-    fn test_forward_substitution() {
-        let mut matrix = Matrix([
-            [1.00, 2.00, 9.00],
-            [0.00, 8.00, 0.00],
-            [6.00, 3.00, 5.00]
+    fn test_inverting_needs_row_swap() {
+        // Rotation of 90 degrees around y; every pivot starts out at 0 (or nearly so)
+        let angle = FRAC_PI_2;
+        let matrix = Matrix([
+            [angle.cos(), 0.0, angle.sin(), 1.0],
+            [0.0, 1.0, 0.0, 2.0],
+            [-angle.sin(), 0.0, angle.cos(), 3.0],
+            [0.0, 0.0, 0.0, 1.0]
         ]);
-        let mut dummy_inv_matrix = Matrix::<3>::identity_matrix();
-        let total_row = 3;
-        let total_column = 3;
-
-        forward_substitution(&mut matrix, &mut dummy_inv_matrix, total_row, total_column);
-        approx_cmp_matrix(matrix, Matrix([
-            [1.00, 2.00, 9.00],
-            [0.00, 8.00, 0.00],
-            [0.00, 0.00, -49.00]
-        ]));
-        approx_cmp_matrix(dummy_inv_matrix, Matrix([
-            [1.00, 0.00, 0.00],
-            [0.00, 1.00, 0.00],
-            [-6.00, 1.125, 1.00]
-        ]))
+
+        let inv_matrix = matrix.invert().unwrap();
+        assert!((&matrix * &inv_matrix).approx_eq(Matrix4::identity_matrix(), (1e-6, 4)));
     }
 
     #[test]
-    fn test_scale_pivot_to_one() {
-        let mut matrix = Matrix([
-            [4.00, 2.00, 9.00],
-            [0.00, 8.00, 7.00],
-            [0.00, 0.00, 5.00]
+    fn test_inverting_singular_matrix() {
+        let singular = Matrix([
+            [1.0, 2.0, 3.0],
+            [2.0, 4.0, 6.0],
+            [0.0, 1.0, 1.0]
         ]);
-        let mut dummy_inv_matrix = Matrix::<3>::identity_matrix();
-        let total_row = 3;
-        let total_column = 3;
-
-        scale_pivot_to_one(&mut matrix, &mut dummy_inv_matrix, total_row, total_column);
-        approx_cmp_matrix(matrix, Matrix([
-            [1.00, 2.00/4.00, 9.00/4.00],
-            [0.00, 1.00, 7.00/8.00],
-            [0.00, 0.00, 1.00]
-        ]));
-        approx_cmp_matrix(dummy_inv_matrix, Matrix([
-            [1.00/4.00, 0.00, 0.00],
-            [0.00, 1.00/8.00, 0.00],
-            [0.00, 0.00, 1.00/5.00],
-        ]));
+        assert_eq!(singular.invert(), Err(MathError::SingularMatrix));
+
+        // Rounding keeps the last pivot from being exactly 0
+        let nearly_singular = Matrix([
+            [0.1, 0.2, 0.3],
+            [0.4, 0.5, 0.6],
+            [0.7, 0.8, 0.9]
+        ]);
+        assert_eq!(nearly_singular.invert(), Err(MathError::SingularMatrix));
+        assert_eq!(Matrix([[0.0; 3]; 3]).invert(), Err(MathError::SingularMatrix));
     }
 
     #[test]
-    fn test_backward_substitution() {
-        let mut matrix = Matrix([
-            [1.0, 0.5, -0.5],
-            [0.0, 1.0, 1.0],
-            [0.0, 0.0, 1.0]
-        ]);
-        let mut dummy_inv_matrix = Matrix([
-            [1.0, 0.0, 0.0],
-            [1.5, 1.0, 0.0],
-            [1.0, 0.0, 1.0]
-        ]);
-        let total_row = 3;
-        let total_column = 3;
-
-        backward_substitution(&mut matrix, &mut dummy_inv_matrix, total_row, total_column);
-        approx_cmp_matrix(matrix, Matrix::<3>::identity_matrix());
-        approx_cmp_matrix(dummy_inv_matrix, Matrix([
-            [1.25, -0.5, 1.0],
-            [0.5, 1.0, -1.0],
-            [1.0, 0.0, 1.0]
-        ]));
+    fn test_inverting_large_translation() {
+        let matrix = Matrix4::from_translation(vector![2e6, -3e6, 1.0]);
+
+        approx_cmp_matrix(matrix.invert().unwrap(), Matrix4::from_translation(vector![-2e6, 3e6, -1.0]));
     }
 
     #[test]
-    fn test_inverting_matrix() {
-        let matrix = Matrix([
-            [1.00, -3.00, 3.01],
-            [4.50, 5.00, 7.00],
-            [3.00, 97.00, 8.00]
+    fn test_inverting_small_scale_with_translation() {
+        let matrix = Matrix4::from_trs(
+            vector![1000.0, 0.0, -1000.0], Quaternion::default(), vector![0.001, 0.001, 0.001]
+        );
+
+        let inv_matrix = matrix.invert().unwrap();
+        assert!(inv_matrix.approx_eq(Matrix([
+            [1000.0, 0.0, 0.0, -1e6],
+            [0.0, 1000.0, 0.0, 0.0],
+            [0.0, 0.0, 1000.0, 1e6],
+            [0.0, 0.0, 0.0, 1.0]
+        ]), (1e-6, 4)));
+    }
+
+    #[test]
+    fn test_multiply_matrix() {
+        let a = Matrix([
+            [1.0, 2.0],
+            [3.0, 4.0]
+        ]);
+        let b = Matrix([
+            [0.0, 1.0],
+            [5.0, -2.0]
         ]);
 
-        let inv_matrix = matrix.invert(false).unwrap();
-        approx_cmp_matrix(inv_matrix, Matrix([
-            [-42600.00/44981.00, 63194.00/134943.00, -7210.00/134943.00],
-            [-1000.00/44981.00, -206.00/134943.00, 1309.00/134943.00],
-            [28100.00/44981.00, -21200.00/134943.00, 3700.00/134943.00]
+        approx_cmp_matrix(&a * &b, Matrix([
+            [10.0, -3.0],
+            [20.0, -5.0]
         ]));
+        approx_cmp_matrix(a.clone() * Matrix::identity_matrix(), a);
     }
 
     #[test]
@@ -372,4 +600,176 @@ pub mod tests {
             (1.0, 2)
         ));
     }
+
+    #[test]
+    fn test_multiply_vector_operator() {
+        let matrix = Matrix([
+            [1.0, 2.0],
+            [3.0, 4.0]
+        ]);
+
+        approx_cmp_vector(&matrix * vector![1.0, -1.0], vector![-1.0, -1.0]);
+        approx_cmp_vector(matrix * vector![0.0, 2.0], vector![4.0, 8.0]);
+    }
+
+    #[test]
+    fn test_add_and_subtract() {
+        let a = Matrix([
+            [1.0, 2.0],
+            [3.0, 4.0]
+        ]);
+        let b = Matrix([
+            [0.5, -1.0],
+            [2.0, 0.0]
+        ]);
+
+        approx_cmp_matrix(&a + &b, Matrix([
+            [1.5, 1.0],
+            [5.0, 4.0]
+        ]));
+        approx_cmp_matrix(a.clone() - b.clone(), Matrix([
+            [0.5, 3.0],
+            [1.0, 4.0]
+        ]));
+        approx_cmp_matrix(a.clone() + b.clone() - b, a);
+    }
+
+    #[test]
+    fn test_rows_and_cols() {
+        let matrix = Matrix3::from_rows([vector![1.0, 2.0, 3.0], vector![4.0, 5.0, 6.0], vector![7.0, 8.0, 9.0]]);
+
+        assert_eq!(matrix[1], [4.0, 5.0, 6.0]);
+        assert_eq!(matrix.row(2), vector![7.0, 8.0, 9.0]);
+        assert_eq!(matrix.col(0), vector![1.0, 4.0, 7.0]);
+        assert_eq!(
+            Matrix3::from_cols([vector![1.0, 2.0, 3.0], vector![4.0, 5.0, 6.0], vector![7.0, 8.0, 9.0]]),
+            matrix.transpose()
+        );
+    }
+
+    #[test]
+    fn test_transpose_and_trace() {
+        let matrix = Matrix([
+            [1.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0],
+            [7.0, 8.0, 9.0]
+        ]);
+
+        assert_eq!(matrix.transpose(), Matrix([
+            [1.0, 4.0, 7.0],
+            [2.0, 5.0, 8.0],
+            [3.0, 6.0, 9.0]
+        ]));
+        assert_eq!(matrix.transpose().transpose(), matrix);
+        assert_eq!(matrix.trace(), 15.0);
+    }
+
+    #[test]
+    fn test_determinant() {
+        let matrix = Matrix([
+            [2.0, 0.0, 1.0],
+            [1.0, 3.0, 2.0],
+            [1.0, 1.0, 2.0]
+        ]);
+        assert!(matrix.determinant().approx_eq(6.0, (1e-5, 4)));
+
+        // Needs a row swap, which flips the sign
+        let matrix = Matrix([
+            [0.0, 1.0],
+            [1.0, 0.0]
+        ]);
+        assert_eq!(matrix.determinant(), -1.0);
+
+        assert_eq!(Matrix([[1.0, 2.0], [2.0, 4.0]]).determinant(), 0.0);
+        assert_eq!(Matrix4::identity_matrix().determinant(), 1.0);
+
+        // Scaling multiplies the volume, rotation keeps it
+        let matrix = Matrix4::from_trs(
+            vector![1.0, 2.0, 3.0], Quaternion::from_euler_angles(0.3, 0.2, 0.1), vector![2.0, 3.0, 4.0]
+        );
+        assert!(matrix.determinant().approx_eq(24.0, (1e-4, 4)));
+    }
+
+    #[test]
+    fn test_transform_builders() {
+        let point = vector![1.0, 2.0, 3.0, 1.0];
+
+        approx_cmp_vector(Matrix4::from_translation(vector![1.0, -1.0, 2.0]) * point, vector![2.0, 1.0, 5.0, 1.0]);
+        approx_cmp_vector(Matrix4::from_scale(vector![2.0, 3.0, -1.0]) * point, vector![2.0, 6.0, -3.0, 1.0]);
+
+        let rotation = Quaternion::from_euler_angles(0.0, FRAC_PI_2, 0.0);
+        let rotated = Matrix4::from_rotation(rotation) * point;
+        assert!(rotated.approx_eq(vector![3.0, 2.0, -1.0, 1.0], (1e-5, 4)));
+
+        // Scale first, then rotate, then translate
+        let translation = vector![1.0, -1.0, 2.0];
+        let scale = vector![2.0, 3.0, -1.0];
+        approx_cmp_matrix(
+            Matrix4::from_trs(translation, rotation, scale),
+            Matrix4::from_translation(translation) * Matrix4::from_rotation(rotation) * Matrix4::from_scale(scale)
+        );
+    }
+
+    #[test]
+    fn test_look_at_matrix() {
+        let eye = vector![6.0, 0.0, -8.0];
+        let view = Matrix4::look_at(eye, vector![0.0, 0.0, 0.0], vector![0.0, 1.0, 0.0]).unwrap();
+
+        // The target is straight ahead, and world up is the top of the screen (-y)
+        let target = &view * vector![0.0, 0.0, 0.0, 1.0];
+        assert!(target.approx_eq(vector![0.0, 0.0, 10.0, 1.0], (1e-5, 4)));
+        let above = &view * vector![0.0, 1.0, 0.0, 1.0];
+        assert!(above.y() < 0.0);
+        assert!(above.x().abs() < 1e-5);
+
+        let eye_in_view = &view * vector![eye.x(), eye.y(), eye.z(), 1.0];
+        assert!(eye_in_view.approx_eq(vector![0.0, 0.0, 0.0, 1.0], (1e-5, 4)));
+
+        assert!(Matrix4::look_at(eye, eye, vector![0.0, 1.0, 0.0]).is_none());
+        assert!(Matrix4::look_at(eye, vector![6.0, 5.0, -8.0], vector![0.0, 1.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn test_decompose_matrix() {
+        let rotation = Quaternion::from_euler_angles(0.4, -1.2, 2.0);
+        let matrix = Matrix4::from_trs(vector![1.0, -2.0, 3.0], rotation, vector![0.5, 2.0, 3.0]);
+        let decomposed = matrix.decompose().unwrap();
+
+        assert!(decomposed.translation.approx_eq(vector![1.0, -2.0, 3.0], (1e-5, 4)));
+        assert!(decomposed.scale.approx_eq(vector![0.5, 2.0, 3.0], (1e-5, 4)));
+        assert!(decomposed.rotation.approx_eq(rotation, (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_decompose_invalid_matrix() {
+        let mut not_affine = Matrix4::identity_matrix();
+        not_affine[3][0] = 1.0;
+        assert_eq!(not_affine.decompose(), Err(MathError::NotAffine));
+
+        let mut zero_scale = Matrix4::identity_matrix();
+        zero_scale[1][1] = 0.0;
+        assert_eq!(zero_scale.decompose(), Err(MathError::ZeroScale(1)));
+
+        let mut sheared = Matrix4::identity_matrix();
+        sheared[0][1] = 0.5;
+        assert_eq!(sheared.decompose(), Err(MathError::Shear));
+
+        let mirrored = Matrix4::from_trs(
+            vector![0.0, 0.0, 0.0], Quaternion::default(), vector![1.0, -1.0, 1.0]
+        );
+        assert_eq!(mirrored.decompose(), Err(MathError::NegativeScale));
+    }
+
+    #[test]
+    fn test_decompose_two_negative_scales() {
+        let matrix = Matrix4::from_trs(
+            vector![0.0, 0.0, 0.0], Quaternion::default(), vector![-2.0, -2.0, 1.0]
+        );
+        let decomposed = matrix.decompose().unwrap();
+
+        // Comes back as a half turn around z instead
+        assert!(decomposed.scale.approx_eq(vector![2.0, 2.0, 1.0], (1e-5, 4)));
+        assert!(Matrix4::from_trs(decomposed.translation, decomposed.rotation, decomposed.scale)
+            .approx_eq(matrix, (1e-5, 4)));
+    }
 }
@@ -1,12 +1,38 @@
 pub mod vector;
 pub mod matrix;
 pub mod quaternion;
+pub mod geometry;
+
+use thiserror::Error;
 
 use matrix::Matrix4;
-use vector::Vector3;
+use vector::{Vector3, Vector4};
 
 
+#[cfg(not(feature = "f64"))]
 pub type FloatType = f32;
+#[cfg(feature = "f64")]
+pub type FloatType = f64;
+
+// Constants such as PI, with the same precision as FloatType
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MathError {
+    #[error("Matrix is not affine; its bottom row must be [0, 0, 0, 1]")]
+    NotAffine,
+    #[error("Matrix has a scale of 0 along axis {0}")]
+    ZeroScale(usize),
+    #[error("Matrix contains shear, which translation, rotation and scale can't represent")]
+    Shear,
+    #[error("Matrix mirrors space (negative scale), which a rotation can't represent")]
+    NegativeScale,
+    #[error("Matrix is singular and has no inverse")]
+    SingularMatrix
+}
 
 // IMPORTANT!
 // Any part of code that deals with angles should be assumed to use radians
@@ -34,11 +60,58 @@ pub fn transform_3d_point(matrix: &Matrix4, vector: Vector3<FloatType>) -> Vecto
     Vector3::new(result_array)
 }
 
+// Like `transform_3d_point`, but leaves out the translation
+pub fn transform_3d_direction(matrix: &Matrix4, direction: Vector3<FloatType>) -> Vector3<FloatType> {
+    let mut result_array = [0.0, 0.0, 0.0];
+
+    for i in 0..Vector3::<FloatType>::SIZE {
+        for j in 0..Vector3::<FloatType>::SIZE {
+            result_array[i] += direction[j] * matrix[i][j];
+        }
+    }
+
+    Vector3::new(result_array)
+}
+
+// Applies a full 4x4 matrix to a homogeneous vector, including the projective (bottom) row
+pub fn transform_homogeneous(matrix: &Matrix4, vector: Vector4<FloatType>) -> Vector4<FloatType> {
+    matrix.multiply_vector(vector)
+}
 
+// Divide by w to get back to 3D space (e.g. from clip space to NDC)
+pub fn perspective_divide(vector: Vector4<FloatType>) -> Vector3<FloatType> {
+    let w = vector[3];
+    Vector3::new([vector[0] / w, vector[1] / w, vector[2] / w])
+}
+
+
+// The matrices below use values rounded to 4 decimal places on purpose
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
     use super::vector::tests::approx_cmp_vector;
+    use float_cmp::ApproxEq;
+
+    #[test]
+    fn test_float_type_matches_feature() {
+        let size = if cfg!(feature = "f64") { 8 } else { 4 };
+        assert_eq!(std::mem::size_of::<FloatType>(), size);
+        assert_eq!(consts::PI, std::f64::consts::PI as FloatType);
+    }
+
+    #[test]
+    fn test_direction_ignores_translation() {
+        let matrix = Matrix4::new([
+            [2.0, 0.0, 0.0, 2.0],
+            [0.0, 1.0, 0.0, 3.0],
+            [0.0, 0.0, 1.0, 4.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let result = transform_3d_direction(&matrix, Vector3::new([1.0, 2.0, 3.0]));
+        approx_cmp_vector(result, Vector3::new([2.0, 2.0, 3.0]));
+    }
 
     #[test]
     fn test_translate_point() {
@@ -84,8 +157,23 @@ mod tests {
 
         let vector = Vector3::new([1.0, 2.0, 3.0]);
         let result = transform_3d_point(&matrix, vector);
-        let expected = Vector3::new([4.1486998, 2.2929, 6.9805]);
+        let expected = Vector3::new([4.1487, 2.2929, 6.9805]);
 
-        approx_cmp_vector(result, expected);
+        assert!(result.approx_eq(expected, (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_homogeneous_round_trip() {
+        let matrix = Matrix4::new([
+            [2.0, 0.0, 0.0, 1.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0]
+        ]);
+
+        let vector = Vector3::new([1.0, 2.0, 4.0]);
+        let clip = transform_homogeneous(&matrix, vector.extend(1.0));
+        approx_cmp_vector(clip, Vector4::new([3.0, 4.0, 8.0, 4.0]));
+        approx_cmp_vector(perspective_divide(clip), Vector3::new([0.75, 1.0, 2.0]));
     }
 }
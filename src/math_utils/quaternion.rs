@@ -3,8 +3,12 @@ use std::default::Default;
 
 use float_cmp::ApproxEq;
 
-use super::matrix::Matrix4;
+use super::matrix::{Matrix3, Matrix4};
 use super::{vector::*, FloatType};
+use super::consts::FRAC_PI_2;
+
+// How close sin(y) has to be to +-1 for `to_euler_angles` to treat it as gimbal lock
+const GIMBAL_LOCK_TOLERANCE: FloatType = 1e-6;
 
 // This implementation of Quaternion does not care about the magnitude of itself. That is, it might
 // not be a unit quaternion.
@@ -97,16 +101,159 @@ impl Quaternion {
         ])
     }
 
-    fn scale(&mut self, num: FloatType) -> () {
+    // Rotation of `angle` radians around `axis`, which does not need to be normalized.
+    // A zero axis gives no rotation.
+    pub fn from_axis_angle(axis: Vector3<FloatType>, angle: FloatType) -> Self {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+
+        Quaternion([cos, axis.x() * sin, axis.y() * sin, axis.z() * sin])
+    }
+
+    // Inverse of `from_axis_angle`, with the angle in [0, PI]. Larger rotations come out as the
+    // equivalent rotation around the opposite axis. Without any rotation the axis is arbitrary,
+    // and +x is returned.
+    pub fn to_axis_angle(&self) -> (Vector3<FloatType>, FloatType) {
+        let mut q = *self;
+        q.normalize();
+        // q and -q are the same rotation; pick the one with the shorter angle
+        if q[0] < 0.0 {
+            q.scale(-1.0);
+        }
+
+        let axis = Vector3::new([q[1], q[2], q[3]]);
+        let sin_half_angle = axis.length();
+        if sin_half_angle <= FloatType::EPSILON {
+            return (Vector3::new([1.0, 0.0, 0.0]), 0.0);
+        }
+
+        // atan2 stays accurate near 0 and PI, where acos(w) does not
+        (axis / sin_half_angle, 2.0 * sin_half_angle.atan2(q[0]))
+    }
+
+    // Inverse of `from_euler_angles`, returned as [x, y, z]. The y angle is kept within
+    // [-PI/2, PI/2]. At exactly +-PI/2 (gimbal lock) x and z rotate around the same axis, and
+    // only their combination is meaningful.
+    pub fn to_euler_angles(&self) -> Vector3<FloatType> {
+        let mut q = *self;
+        q.normalize();
+        let [w, x, y, z] = q.0;
+        let sin_y = 2.0 * (w * y - x * z);
+
+        // Both atan2 below would be atan2(~0, ~0), so put the whole rotation into z instead
+        if sin_y.abs() >= 1.0 - GIMBAL_LOCK_TOLERANCE {
+            let sign = sin_y.signum();
+            return Vector3::new([0.0, sign * FRAC_PI_2, -2.0 * sign * x.atan2(w)]);
+        }
+
+        let x_angle = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let y_angle = sin_y.asin();
+        let z_angle = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+        Vector3::new([x_angle, y_angle, z_angle])
+    }
+
+    // Expects a pure rotation matrix (orthonormal, determinant of 1).
+    // Picks the largest of w, x, y, z to divide with, to avoid precision loss. See:
+    // https://www.euclideanspace.com/maths/geometry/rotations/conversions/matrixToQuaternion/
+    pub fn from_rotation_matrix(m: &Matrix3) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion([
+                0.25 * s,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s
+            ])
+        }
+        else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Quaternion([
+                (m[2][1] - m[1][2]) / s,
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s
+            ])
+        }
+        else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Quaternion([
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s
+            ])
+        }
+        else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Quaternion([
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s
+            ])
+        }
+    }
+
+    pub fn dot(&self, other: Quaternion) -> FloatType {
+        (0..4).map(|i| self[i] * other[i]).sum()
+    }
+
+    // Spherical linear interpolation, always taking the shortest path between the two
+    // rotations. Both quaternions are expected to be unit quaternions.
+    // See: https://en.wikipedia.org/wiki/Slerp
+    pub fn slerp(&self, other: Quaternion, t: FloatType) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut end = other;
+
+        // q and -q are the same rotation, but only one of them is the short way around
+        if cos_theta < 0.0 {
+            end.scale(-1.0);
+            cos_theta = -cos_theta;
+        }
+
+        let (self_weight, end_weight) = if cos_theta > 0.9995 {
+            // Nearly identical, fall back to linear interpolation to avoid dividing by ~0
+            (1.0 - t, t)
+        }
+        else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        let mut result = Quaternion([0.0; 4]);
+        for i in 0..4 {
+            result[i] = self[i] * self_weight + end[i] * end_weight;
+        }
+        result.normalize();
+        result
+    }
+
+    // Normalized linear interpolation, also along the shortest path. Cheaper than slerp, but
+    // the rotation speeds up towards the middle.
+    pub fn nlerp(&self, other: Quaternion, t: FloatType) -> Quaternion {
+        let sign = if self.dot(other) < 0.0 { -1.0 } else { 1.0 };
+
+        let mut result = Quaternion([0.0; 4]);
+        for i in 0..4 {
+            result[i] = self[i] * (1.0 - t) + other[i] * sign * t;
+        }
+        result.normalize();
+        result
+    }
+
+    fn scale(&mut self, num: FloatType) {
         self.0.iter_mut().for_each(|x| *x *= num);
     }
 
-    pub fn normalize(&mut self) -> () {
+    pub fn normalize(&mut self) {
         let sq_magnitude = self.sq_magnitude();
-        // The epsilon value is calculated by:
-        // https://johannesugb.github.io/cpu-programming/tools/floating-point-epsilon-calculator/
-        // Might not be the perfect value
-        if (1.0 - sq_magnitude).abs() < 1.19209e-07 {
+        // Only close enough to 1 for the approximation below when the error is within machine
+        // epsilon of FloatType
+        if (1.0 - sq_magnitude).abs() < FloatType::EPSILON {
             // Based on Padé approximation. See:
             // https://stackoverflow.com/questions/11667783/quaternion-and-normalization
             self.scale(2.0 / (1.0 + sq_magnitude));
@@ -116,7 +263,31 @@ impl Quaternion {
         }
     }
 
-    pub fn edit_3d_matrix(&self, matrix: &mut Matrix4, original_scale: Vector3<FloatType>) -> () {
+    // Inverse rotation, as long as the quaternion has unit length
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion([self[0], -self[1], -self[2], -self[3]])
+    }
+
+    // Works for quaternions of any non-zero length, unlike `conjugate`
+    pub fn inverse(&self) -> Quaternion {
+        let mut inverse = self.conjugate();
+        inverse.scale(1.0 / self.sq_magnitude());
+        inverse
+    }
+
+    // Same as q * v * q^-1, but cheaper. The formula only holds for a unit quaternion, so it works
+    // on a normalized copy. See:
+    // https://fgiesen.wordpress.com/2019/02/09/rotating-a-single-vector-using-a-quaternion/
+    pub fn rotate_vector(&self, vector: Vector3<FloatType>) -> Vector3<FloatType> {
+        let mut q = *self;
+        q.normalize();
+
+        let axis = Vector3::new([q[1], q[2], q[3]]);
+        let t = axis.cross(vector) * 2.0;
+        vector + t * q[0] + axis.cross(t)
+    }
+
+    pub fn to_rotation_matrix(&self) -> Matrix3 {
         let wx = self[0] * self[1] * 2.0;
         let wy = self[0] * self[2] * 2.0;
         let wz = self[0] * self[3] * 2.0;
@@ -130,51 +301,62 @@ impl Quaternion {
 
         let zz = self[3] * self[3] * 2.0;
 
-        matrix[0][0] = (1.0 - yy - zz) * original_scale.x();
-        matrix[0][1] = xy - wz;
-        matrix[0][2] = xz + wy;
-        matrix[1][0] = xy + wz;
-        matrix[1][1] = (1.0 - xx - zz) * original_scale.y();
-        matrix[1][2] = yz - wx;
-        matrix[2][0] = xz - wy;
-        matrix[2][1] = yz + wx;
-        matrix[2][2] = (1.0 - xx - yy) * original_scale.z();
+        Matrix3::new([
+            [1.0 - yy - zz, xy - wz, xz + wy],
+            [xy + wz, 1.0 - xx - zz, yz - wx],
+            [xz - wy, yz + wx, 1.0 - xx - yy]
+        ])
+    }
+
+    // Writes rotation * scale into the upper-left 3x3 part of `matrix`, so scaling happens
+    // first, along the object's own axes. The translation column is left untouched.
+    pub fn edit_3d_matrix(&self, matrix: &mut Matrix4, scale: Vector3<FloatType>) {
+        let rotation = self.to_rotation_matrix();
+
+        for row in 0..3 {
+            for col in 0..3 {
+                matrix[row][col] = rotation[row][col] * scale[col];
+            }
+        }
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::math_utils::vector::tests::approx_cmp_vector;
+    use super::super::consts::{FRAC_PI_4, PI};
 
     use super::*;
+    use super::super::vector::tests::approx_cmp_vector;
     use float_cmp::approx_eq;
 
     pub fn approx_cmp_quaternion(q1: Quaternion, q2: Quaternion) {
         assert!(approx_eq!(Quaternion, q1, q2))
     }
 
+    // Expected values in these tests are only as precise as f32, so they are compared with an
+    // absolute margin that also works with the f64 feature
     #[test]
     fn quaternion_from_euler_angle_x_only() {
         let q = Quaternion::from_euler_angles(1.0, 0.0, 0.0);
-        approx_cmp_quaternion(q, Quaternion([0.87758255, 0.47942555, 0.0, 0.0]));
+        assert!(q.approx_eq(Quaternion([0.87758255, 0.47942555, 0.0, 0.0]), (1e-6, 4)));
     }
 
     #[test]
     fn quaternion_from_euler_angle_y_only() {
         let q = Quaternion::from_euler_angles(0.0, 1.0, 0.0);
-        approx_cmp_quaternion(q, Quaternion([0.87758255, 0.0, 0.47942555, 0.0]));
+        assert!(q.approx_eq(Quaternion([0.87758255, 0.0, 0.47942555, 0.0]), (1e-6, 4)));
     }
 
     #[test]
     fn quaternion_from_euler_angle_z_only() {
         let q = Quaternion::from_euler_angles(0.0, 0.0, 1.0);
-        approx_cmp_quaternion(q, Quaternion([0.87758255, 0.0, 0.0, 0.47942555]));
+        assert!(q.approx_eq(Quaternion([0.87758255, 0.0, 0.0, 0.47942555]), (1e-6, 4)));
     }
 
     #[test]
     fn quaternion_from_euler_angle_all() {
         let q = Quaternion::from_euler_angles(1.0, 1.0, 1.0);
-        approx_cmp_quaternion(q, Quaternion([0.7860666, 0.16751876, 0.5709415, 0.1675188]));
+        assert!(q.approx_eq(Quaternion([0.7860666, 0.16751876, 0.5709415, 0.1675188]), (1e-6, 4)));
     }
 
     #[test]
@@ -200,6 +382,7 @@ pub mod tests {
     }
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn test_edit_matrix4_rotation() {
         // Value based on Euler angle (45, 45, 90). See
         // https://www.andre-gaschler.com/rotationconverter/
@@ -214,7 +397,172 @@ pub mod tests {
                 [0.9904334, -0.1214509, 0.0655087, 0.0],
                 [0.0655087, 0.8316519, 0.5514197, 0.0],
                 [0.0, 0.0, 0.0, 1.0]
-            ]))
+            ]), epsilon = 1e-6)
         );
     }
+
+    #[test]
+    fn test_quaternion_from_rotation_matrix() {
+        // Same rotation as `test_edit_matrix4_rotation`
+        let mat = Matrix3::new([
+            [-0.1214509, -0.541853, 0.8316519],
+            [0.9904334, -0.1214509, 0.0655087],
+            [0.0655087, 0.8316519, 0.5514197]
+        ]);
+
+        let q = Quaternion::from_rotation_matrix(&mat);
+        assert!(q.approx_eq(Quaternion([0.5719523, 0.3348807, 0.3348807, 0.6697614]), (1e-6, 2)));
+    }
+
+    #[test]
+    fn test_quaternion_from_half_turn_matrix() {
+        // 180 degrees around the x axis has a trace of -1
+        let mat = Matrix3::new([
+            [1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, -1.0]
+        ]);
+
+        approx_cmp_quaternion(Quaternion::from_rotation_matrix(&mat), Quaternion([0.0, 1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_slerp() {
+        let start = Quaternion::default();
+        let end = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+
+        approx_cmp_quaternion(start.slerp(end, 0.0), start);
+        approx_cmp_quaternion(start.slerp(end, 1.0), end);
+        assert!(start.slerp(end, 0.5).approx_eq(
+            Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_4),
+            (1e-6, 2)
+        ));
+    }
+
+    #[test]
+    fn test_slerp_takes_shortest_path() {
+        let start = Quaternion::default();
+        // Same rotation as `start`, but on the other side of the hypersphere
+        let end = Quaternion([-1.0, 0.0, 0.0, 0.0]);
+
+        assert!(start.slerp(end, 0.5).approx_eq(start, (1e-6, 2)));
+    }
+
+    #[test]
+    fn test_edit_matrix4_non_uniform_scale() {
+        // 90 degrees around z, which maps x to y
+        let q = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+        let mut mat = Matrix4::identity_matrix();
+
+        q.edit_3d_matrix(&mut mat, Vector3::new([2.0, 3.0, 4.0]));
+
+        // Each column is a rotated axis, stretched by the scale along that axis
+        assert!(
+            approx_eq!(Matrix4, mat, Matrix4::new([
+                [0.0, -3.0, 0.0, 0.0],
+                [2.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 4.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0]
+            ]), epsilon = 1e-6)
+        );
+    }
+
+    #[test]
+    fn test_conjugate_undoes_rotation() {
+        let q = Quaternion::from_euler_angles(0.3, -0.7, 1.1);
+
+        approx_cmp_quaternion(q * q.conjugate(), Quaternion::default());
+    }
+
+    #[test]
+    fn test_inverse() {
+        let q = Quaternion::from_euler_angles(0.3, -0.7, 1.1);
+        let mut scaled = q;
+        scaled.scale(3.0);
+
+        approx_cmp_quaternion(q.inverse(), q.conjugate());
+        assert!((scaled * scaled.inverse()).approx_eq(Quaternion::default(), (1e-6, 4)));
+    }
+
+    #[test]
+    fn test_axis_angle_round_trip() {
+        let axis = Vector3::new([1.0, -2.0, 2.0]);
+        let q = Quaternion::from_axis_angle(axis, 1.2);
+        let (result_axis, angle) = q.to_axis_angle();
+
+        approx_cmp_vector(result_axis, axis.normalized());
+        assert!(angle.approx_eq(1.2, (1e-6, 4)));
+
+        // Going around the long way is the same as a shorter turn around the opposite axis
+        let (result_axis, angle) = Quaternion::from_axis_angle(axis, 5.0).to_axis_angle();
+        assert!(result_axis.approx_eq(-axis.normalized(), (1e-5, 4)));
+        assert!(angle.approx_eq(2.0 * PI - 5.0, (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_axis_angle_matches_euler_angles() {
+        let q = Quaternion::from_axis_angle(Vector3::new([0.0, 0.0, 1.0]), FRAC_PI_2);
+        approx_cmp_quaternion(q, Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2));
+
+        let (axis, angle) = Quaternion::default().to_axis_angle();
+        assert_eq!((axis, angle), (Vector3::new([1.0, 0.0, 0.0]), 0.0));
+    }
+
+    #[test]
+    fn test_euler_angles_round_trip() {
+        let angles = Vector3::new([0.3, -0.7, 1.1]);
+        let q = Quaternion::from_euler_angles(angles.x(), angles.y(), angles.z());
+
+        assert!(q.to_euler_angles().approx_eq(angles, (1e-5, 4)));
+
+        // Only the direction of the quaternion matters
+        let mut scaled = q;
+        scaled.scale(3.0);
+        assert!(scaled.to_euler_angles().approx_eq(angles, (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_euler_angles_gimbal_lock() {
+        for y in [FRAC_PI_2, -FRAC_PI_2] {
+            let q = Quaternion::from_euler_angles(0.4, y, -0.3);
+            let angles = q.to_euler_angles();
+
+            assert_eq!(angles.y(), y);
+            // The angles differ, but they must describe the same rotation
+            let result = Quaternion::from_euler_angles(angles.x(), angles.y(), angles.z());
+            assert!(result.dot(q).abs().approx_eq(1.0, (1e-5, 4)));
+        }
+    }
+
+    #[test]
+    fn test_rotate_vector() {
+        let q = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+        let rotated = q.rotate_vector(Vector3::new([1.0, 2.0, 3.0]));
+        assert!(rotated.approx_eq(Vector3::new([-2.0, 1.0, 3.0]), (1e-5, 4)));
+
+        // Same as going through the rotation matrix
+        let q = Quaternion::from_euler_angles(0.3, -0.7, 1.1);
+        let vector = Vector3::new([1.0, -2.0, 0.5]);
+        assert!(q.rotate_vector(vector).approx_eq(q.to_rotation_matrix().multiply_vector(vector), (1e-5, 4)));
+
+        // A quaternion that drifted away from unit length still only rotates
+        let mut scaled = q;
+        scaled.scale(0.5);
+        assert!(scaled.rotate_vector(vector).approx_eq(q.rotate_vector(vector), (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_nlerp() {
+        let start = Quaternion::default();
+        let end = Quaternion::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+
+        approx_cmp_quaternion(start.nlerp(end, 0.0), start);
+        approx_cmp_quaternion(start.nlerp(end, 1.0), end);
+        // Halfway is the same as slerp, only the speed in between differs
+        assert!(start.nlerp(end, 0.5).approx_eq(start.slerp(end, 0.5), (1e-6, 4)));
+
+        // Shortest path
+        let flipped = Quaternion([-1.0, 0.0, 0.0, 0.0]);
+        assert!(start.nlerp(flipped, 0.5).approx_eq(start, (1e-6, 2)));
+    }
 }
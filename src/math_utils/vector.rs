@@ -1,4 +1,5 @@
-use std::{fmt::Debug, ops::{Index, IndexMut, Add, Sub}};
+use std::{fmt::Debug, ops::{Index, IndexMut, Add, Sub, Mul, Div, Neg}};
+use std::ops::{AddAssign, SubAssign, MulAssign, DivAssign};
 use std::default::Default;
 
 use num_traits::{Float, Num};
@@ -37,21 +38,131 @@ where T: Num + Copy {
         self.0[2]
     }
 
-    pub fn set_x(&mut self, value: T) -> () {
+    pub fn set_x(&mut self, value: T) {
         self.0[0] = value;
     }
 
-    pub fn set_y(&mut self, value: T) -> () {
+    pub fn set_y(&mut self, value: T) {
         self.0[1] = value;
     }
 
-    pub fn set_z(&mut self, value: T) -> () {
+    pub fn set_z(&mut self, value: T) {
         self.0[2] = value;
     }
 
     pub fn array(&self) -> &[T; N] {
         &self.0
     }
+
+    pub fn dot(&self, other: Self) -> T {
+        let mut sum = T::zero();
+        for i in 0..N {
+            sum = sum + self.0[i] * other.0[i];
+        }
+
+        sum
+    }
+
+    // Cheaper than `length` when only comparing distances
+    pub fn sq_length(&self) -> T {
+        self.dot(*self)
+    }
+
+    // `t` = 0 gives `self` and `t` = 1 gives `other`. Values outside [0, 1] extrapolate.
+    pub fn lerp(&self, other: Self, t: T) -> Self {
+        *self + (other - *self) * t
+    }
+
+    pub fn map(&self, f: impl FnMut(T) -> T) -> Self {
+        Self(self.0.map(f))
+    }
+}
+
+impl<const N: usize, T: Num + Copy + PartialOrd> Vector<N,T> {
+    // Component-wise minimum
+    pub fn min(&self, other: Self) -> Self {
+        let mut output = *self;
+        for i in 0..N {
+            if other.0[i] < output.0[i] {
+                output.0[i] = other.0[i];
+            }
+        }
+
+        output
+    }
+
+    // Component-wise maximum
+    pub fn max(&self, other: Self) -> Self {
+        let mut output = *self;
+        for i in 0..N {
+            if other.0[i] > output.0[i] {
+                output.0[i] = other.0[i];
+            }
+        }
+
+        output
+    }
+}
+
+impl<const N: usize, T: Float> Vector<N,T> {
+    pub fn length(&self) -> T {
+        self.sq_length().sqrt()
+    }
+
+    // Zero-length vectors are left as is, since they have no direction
+    pub fn normalize(&mut self) {
+        let length = self.length();
+        if length == T::zero() {
+            return;
+        }
+
+        self.0.iter_mut().for_each(|x| *x = *x / length);
+    }
+
+    pub fn normalized(&self) -> Self {
+        let mut output = *self;
+        output.normalize();
+        output
+    }
+}
+
+impl<T: Num + Copy> Vector3<T> {
+    pub fn cross(&self, other: Self) -> Self {
+        Self([
+            self.y() * other.z() - self.z() * other.y(),
+            self.z() * other.x() - self.x() * other.z(),
+            self.x() * other.y() - self.y() * other.x()
+        ])
+    }
+}
+
+/* Conversions between sizes */
+impl<T: Num + Copy> Vector2<T> {
+    pub fn extend(&self, z: T) -> Vector3<T> {
+        Vector::new([self.x(), self.y(), z])
+    }
+}
+
+impl<T: Num + Copy> Vector3<T> {
+    // Use w = 1 for points and w = 0 for directions
+    pub fn extend(&self, w: T) -> Vector4<T> {
+        Vector::new([self.x(), self.y(), self.z(), w])
+    }
+
+    pub fn truncate(&self) -> Vector2<T> {
+        Vector::new([self.x(), self.y()])
+    }
+}
+
+impl<T: Num + Copy> Vector4<T> {
+    pub fn w(&self) -> T {
+        self.0[3]
+    }
+
+    // Drops w without dividing by it, see `perspective_divide` for that
+    pub fn truncate(&self) -> Vector3<T> {
+        Vector::new([self.x(), self.y(), self.z()])
+    }
 }
 
 impl<const N: usize, T: Num + Copy> Default for Vector<N,T> {
@@ -113,6 +224,51 @@ impl<const N: usize, T: Num + Copy> Sub for Vector<N, T> {
     }
 }
 
+impl<const N: usize, T: Num + Copy> Mul<T> for Vector<N, T> {
+    type Output = Vector<N, T>;
+    fn mul(self, rhs: T) -> Self::Output {
+        self.map(|c| c * rhs)
+    }
+}
+
+impl<const N: usize, T: Num + Copy> Div<T> for Vector<N, T> {
+    type Output = Vector<N, T>;
+    fn div(self, rhs: T) -> Self::Output {
+        self.map(|c| c / rhs)
+    }
+}
+
+impl<const N: usize, T: Num + Copy + Neg<Output = T>> Neg for Vector<N, T> {
+    type Output = Vector<N, T>;
+    fn neg(self) -> Self::Output {
+        self.map(|c| -c)
+    }
+}
+
+impl<const N: usize, T: Num + Copy> AddAssign for Vector<N, T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<const N: usize, T: Num + Copy> SubAssign for Vector<N, T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<const N: usize, T: Num + Copy> MulAssign<T> for Vector<N, T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const N: usize, T: Num + Copy> DivAssign<T> for Vector<N, T> {
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
+}
+
 impl<const N: usize, T> Debug for Vector<N,T>
 where T: Debug {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -151,4 +307,94 @@ pub mod tests {
     (vec1: Vector<N,T>, vec2: Vector<N,T>) {
         assert!(approx_eq!(Vector<N,T>, vec1, vec2));
     }
+
+    #[test]
+    fn test_dot_product() {
+        let v1: Vector3<f32> = vector![1.0, 2.0, 3.0];
+        let v2 = vector![4.0, -5.0, 6.0];
+
+        assert_eq!(v1.dot(v2), 12.0);
+    }
+
+    #[test]
+    fn test_cross_product() {
+        let x: Vector3<f32> = vector![1.0, 0.0, 0.0];
+        let y = vector![0.0, 1.0, 0.0];
+
+        approx_cmp_vector(x.cross(y), vector![0.0, 0.0, 1.0]);
+        approx_cmp_vector(y.cross(x), vector![0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let v: Vector3<f32> = vector![3.0, 0.0, 4.0];
+
+        assert_eq!(v.length(), 5.0);
+        approx_cmp_vector(v.normalized(), vector![0.6, 0.0, 0.8]);
+        approx_cmp_vector(Vector3::<f32>::default().normalized(), Vector3::default());
+    }
+
+    #[test]
+    fn test_sq_length() {
+        let v: Vector3<f32> = vector![1.0, -2.0, 2.0];
+
+        assert_eq!(v.sq_length(), 9.0);
+        assert_eq!(v.length(), 3.0);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a: Vector3<f32> = vector![0.0, 2.0, -4.0];
+        let b = vector![2.0, 4.0, 4.0];
+
+        approx_cmp_vector(a.lerp(b, 0.0), a);
+        approx_cmp_vector(a.lerp(b, 1.0), b);
+        approx_cmp_vector(a.lerp(b, 0.25), vector![0.5, 2.5, -2.0]);
+        approx_cmp_vector(a.lerp(b, 2.0), vector![4.0, 6.0, 12.0]);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let a: Vector3<f32> = vector![1.0, 5.0, -3.0];
+        let b = vector![2.0, -1.0, -3.0];
+
+        assert_eq!(a.min(b), vector![1.0, -1.0, -3.0]);
+        assert_eq!(a.max(b), vector![2.0, 5.0, -3.0]);
+    }
+
+    #[test]
+    fn test_scalar_operators() {
+        let v: Vector3<f32> = vector![1.0, -2.0, 4.0];
+
+        approx_cmp_vector(v * 2.0, vector![2.0, -4.0, 8.0]);
+        approx_cmp_vector(v / 4.0, vector![0.25, -0.5, 1.0]);
+        approx_cmp_vector(-v, vector![-1.0, 2.0, -4.0]);
+    }
+
+    #[test]
+    fn test_assign_operators() {
+        let mut v: Vector3<f32> = vector![1.0, 2.0, 3.0];
+
+        v += vector![1.0, 1.0, 1.0];
+        approx_cmp_vector(v, vector![2.0, 3.0, 4.0]);
+        v -= vector![2.0, 0.0, 1.0];
+        approx_cmp_vector(v, vector![0.0, 3.0, 3.0]);
+        v *= 2.0;
+        approx_cmp_vector(v, vector![0.0, 6.0, 6.0]);
+        v /= 3.0;
+        approx_cmp_vector(v, vector![0.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn test_extend_and_truncate() {
+        let v2: Vector2<f32> = vector![1.0, 2.0];
+        let v3 = v2.extend(3.0);
+        let v4 = v3.extend(1.0);
+
+        assert_eq!(v3, vector![1.0, 2.0, 3.0]);
+        assert_eq!(v4, vector![1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(v4.w(), 1.0);
+        assert_eq!(v4.truncate(), v3);
+        assert_eq!(v3.truncate(), v2);
+    }
 }
@@ -3,19 +3,47 @@ use std::vec;
 use tobj::{load_obj, LoadError};
 
 use crate::transform::Transform;
+use crate::animation::TransformAnimation;
 use crate::math_utils;
 use math_utils::FloatType;
 use math_utils::vector::Vector3;
+use math_utils::geometry::{Aabb, Ray, Sphere, Triangle};
 
 
-#[derive(Debug)]
+// A node of the scene graph. Children are positioned relative to their parent, so moving an
+// object moves everything attached to it.
+#[derive(Debug, Default)]
 pub struct Object {
     pub transform: Transform,
-    pub mesh: Mesh
+    pub mesh: Mesh,
+    pub children: Vec<Object>,
+    pub animation: Option<TransformAnimation>
 }
 
 pub type Face = [u32; 3];
 
+// Closest intersection between a ray and an object's mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub face_index: usize,
+    // Weights of the face's 3 vertices, in the same order as in `Face`
+    pub barycentric: Vector3<FloatType>,
+    pub position: Vector3<FloatType>,
+    // In multiples of the ray's direction
+    pub distance: FloatType
+}
+
+// Where an object sits in the scene graph: the index of the child to follow at each level,
+// starting from the root. Empty for the root itself.
+pub type ObjectPath = Vec<usize>;
+
+// Closest intersection between a ray and any object of a subtree
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickHit {
+    pub path: ObjectPath,
+    pub hit: RayHit
+}
+
 #[derive(Debug)]
 pub struct Mesh {
     pub vertices: Vec<Vector3<FloatType>>,
@@ -31,7 +59,133 @@ impl Mesh {
     }
 }
 
+impl Mesh {
+    // Both bounds are in object space, and are computed from scratch on every call
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(&self.vertices)
+    }
+
+    // Centered on the AABB, which is not the tightest sphere possible but is cheap to find
+    pub fn bounding_sphere(&self) -> Option<Sphere> {
+        let center = self.aabb()?.center();
+        let radius = self.vertices.iter()
+            .map(|vert| (*vert - center).length())
+            .fold(0.0, FloatType::max);
+
+        Some(Sphere::new(center, radius))
+    }
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Object {
+    pub fn from_mesh(mesh: Mesh) -> Self {
+        Self {
+            transform: Transform::default(),
+            mesh,
+            children: vec![],
+            animation: None
+        }
+    }
+
+    pub fn add_child(&mut self, child: Object) {
+        self.children.push(child);
+    }
+
+    // Updates the transform of this object and all of its descendants.
+    // Only dirty transforms are recomputed, and a change anywhere marks the whole subtree below
+    // it as dirty, since their world matrices depend on it.
+    pub fn update_transforms(&mut self) {
+        let is_changed = self.transform.is_dirty();
+        self.transform.update();
+
+        for child in &mut self.children {
+            if is_changed {
+                child.transform.set_parent_matrix(
+                    self.transform.local_to_world_matrix(),
+                    self.transform.world_to_local_matrix()
+                );
+            }
+            child.update_transforms();
+        }
+    }
+
+    // Poses this object and its descendants as their animations are at `time` seconds.
+    // Call `update_transforms` afterwards.
+    pub fn animate(&mut self, time: FloatType) {
+        if let Some(animation) = &self.animation {
+            animation.apply(&mut self.transform, time);
+        }
+
+        for child in &mut self.children {
+            child.animate(time);
+        }
+    }
+
+    // Number of objects in this subtree, including this one
+    pub fn count(&self) -> usize {
+        1 + self.children.iter().map(Object::count).sum::<usize>()
+    }
+
+    // Only this object's own mesh is tested, not its children's.
+    // `ray` is in world space. Faces are tested one by one, there's no acceleration structure.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<RayHit> {
+        let world_vertices: Vec<Vector3<FloatType>> = self.mesh.vertices.iter()
+            .map(|vert| self.transform.local_to_world(*vert))
+            .collect();
+
+        let mut closest_hit: Option<RayHit> = None;
+        for (face_index, face) in self.mesh.faces.iter().enumerate() {
+            let [a, b, c] = face.map(|i| world_vertices[i as usize]);
+            let Some(hit) = ray.intersect_triangle(&Triangle::new(a, b, c)) else {
+                continue;
+            };
+
+            if closest_hit.is_some_and(|closest| closest.distance <= hit.distance) {
+                continue;
+            }
+
+            closest_hit = Some(RayHit {
+                face_index,
+                barycentric: hit.barycentric,
+                position: ray.point_at(hit.distance),
+                distance: hit.distance
+            });
+        }
+
+        closest_hit
+    }
+
+    // Like `intersect_ray`, but over this object and all of its descendants.
+    // The path is relative to this object.
+    pub fn pick(&self, ray: &Ray) -> Option<PickHit> {
+        let mut closest_hit = self.intersect_ray(ray).map(|hit| PickHit { path: vec![], hit });
+
+        for (index, child) in self.children.iter().enumerate() {
+            let Some(mut child_hit) = child.pick(ray) else {
+                continue;
+            };
+
+            if closest_hit.as_ref().is_some_and(|closest| closest.hit.distance <= child_hit.hit.distance) {
+                continue;
+            }
+
+            child_hit.path.insert(0, index);
+            closest_hit = Some(child_hit);
+        }
+
+        closest_hit
+    }
+
+    // Bounding sphere of the mesh, in world space
+    pub fn world_bounding_sphere(&self) -> Option<Sphere> {
+        Some(self.mesh.bounding_sphere()?.transformed(self.transform.local_to_world_matrix()))
+    }
+
     pub fn new(filename: &str) -> Result<Self, LoadError> {
         let (mut models, _) = load_obj(filename, &tobj::GPU_LOAD_OPTIONS)?;
         let tobj_mesh = models.swap_remove(0).mesh;
@@ -66,10 +220,122 @@ impl Object {
 
         println!("Processed mesh:\n{:?}", mesh);
 
-        Ok(Self {
-            transform: Transform::default(),
-            // Get the first model, since we are assuming there will only be 1 mesh
+        // Get the first model, since we are assuming there will only be 1 mesh
+        Ok(Self::from_mesh(mesh))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use float_cmp::ApproxEq;
+    use math_utils::vector::{vector, Vector};
+    use math_utils::vector::tests::approx_cmp_vector;
+    use math_utils::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_intersect_ray_picks_closest_face() {
+        let mut mesh = Mesh::new();
+        mesh.vertices = vec![
+            vector![-1.0, -1.0, 0.0], vector![1.0, -1.0, 0.0], vector![0.0, 1.0, 0.0],
+            vector![-1.0, -1.0, 2.0], vector![1.0, -1.0, 2.0], vector![0.0, 1.0, 2.0]
+        ];
+        mesh.faces = vec![[3, 4, 5], [0, 1, 2]];
+        let mut obj = Object::from_mesh(mesh);
+        obj.transform.set_position(vector![0.0, 0.0, 5.0]);
+        obj.transform.update();
+
+        let ray = Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        let hit = obj.intersect_ray(&ray).unwrap();
+
+        assert_eq!(hit.face_index, 1);
+        assert_eq!(hit.distance, 5.0);
+        approx_cmp_vector(hit.position, vector![0.0, 0.0, 5.0]);
+        approx_cmp_vector(hit.barycentric, vector![0.25, 0.25, 0.5]);
+
+        let miss = Ray::new(vector![5.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        assert_eq!(obj.intersect_ray(&miss), None);
+    }
+
+    #[test]
+    fn test_pick_searches_children() {
+        let triangle = || {
+            let mut mesh = Mesh::new();
+            mesh.vertices = vec![vector![-1.0, -1.0, 0.0], vector![1.0, -1.0, 0.0], vector![0.0, 1.0, 0.0]];
+            mesh.faces = vec![[0, 1, 2]];
             mesh
-        })
+        };
+
+        // The grandchild sits in front of its ancestors, which are both in the way of the ray
+        let mut grandchild = Object::from_mesh(triangle());
+        grandchild.transform.set_position(vector![0.0, 0.0, -4.0]);
+        let mut child = Object::from_mesh(triangle());
+        child.transform.set_position(vector![0.0, 0.0, 2.0]);
+        child.add_child(grandchild);
+        let mut root = Object::from_mesh(triangle());
+        root.transform.set_position(vector![0.0, 0.0, 10.0]);
+        root.add_child(Object::default());
+        root.add_child(child);
+        root.update_transforms();
+
+        let ray = Ray::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        let pick = root.pick(&ray).unwrap();
+        assert_eq!(pick.path, vec![1, 0]);
+        approx_cmp_vector(pick.hit.position, vector![0.0, 0.0, 8.0]);
+        // Only the root's own mesh
+        assert_eq!(root.intersect_ray(&ray).unwrap().distance, 10.0);
+
+        let miss = Ray::new(vector![5.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]);
+        assert_eq!(root.pick(&miss), None);
+    }
+
+    #[test]
+    fn test_children_follow_parent() {
+        let mut moon = Object::default();
+        moon.transform.set_position(vector![2.0, 0.0, 0.0]);
+        let mut planet = Object::default();
+        planet.transform.set_position(vector![0.0, 0.0, 10.0]);
+        planet.add_child(moon);
+        planet.update_transforms();
+
+        let moon_transform = &planet.children[0].transform;
+        approx_cmp_vector(moon_transform.local_to_world(vector![0.0, 0.0, 0.0]), vector![2.0, 0.0, 10.0]);
+
+        // A quarter turn of the planet around y carries the moon from +x to -z
+        planet.transform.set_rotation(
+            crate::math_utils::quaternion::Quaternion::from_euler_angles(0.0, FRAC_PI_2, 0.0)
+        );
+        planet.update_transforms();
+
+        let moon_transform = &planet.children[0].transform;
+        assert!(moon_transform.local_to_world(vector![0.0, 0.0, 0.0]).approx_eq(vector![0.0, 0.0, 8.0], (1e-5, 4)));
+        #[cfg(not(feature = "f64"))]
+        approx_cmp_vector(moon_transform.world_to_local(vector![0.0, 0.0, 8.0]), vector![0.0, 0.0, 0.0]);
+        // In double precision, the quarter turn itself is an ulp off, which puts the moon about
+        // 4e-16 away from (0, 0, 8). That is more than the default margin around 0.
+        #[cfg(feature = "f64")]
+        assert!(moon_transform.world_to_local(vector![0.0, 0.0, 8.0]).approx_eq(vector![0.0, 0.0, 0.0], (1e-12, 4)));
+        assert_eq!(planet.count(), 2);
+    }
+
+    #[test]
+    fn test_grandchildren_are_updated() {
+        let mut grandchild = Object::default();
+        grandchild.transform.set_position(vector![0.0, 0.0, 1.0]);
+        let mut child = Object::default();
+        child.transform.set_position(vector![0.0, 1.0, 0.0]);
+        child.add_child(grandchild);
+        let mut root = Object::default();
+        root.add_child(child);
+        root.update_transforms();
+
+        root.transform.set_position(vector![1.0, 0.0, 0.0]);
+        root.update_transforms();
+
+        let grandchild = &root.children[0].children[0];
+        assert!(!grandchild.transform.is_dirty());
+        approx_cmp_vector(grandchild.transform.local_to_world(vector![0.0, 0.0, 0.0]), vector![1.0, 1.0, 1.0]);
     }
 }
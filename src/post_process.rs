@@ -0,0 +1,467 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::math_utils::FloatType;
+use crate::renderer::Color;
+
+// Each pixel is held as linear [r, g, b] in range 0.0..=1.0 while the passes run.
+// Converting back to u32 only happens once, after the last pass, so chaining passes
+// does not accumulate rounding errors.
+type Pixel = [FloatType; 3];
+
+// Ordered dithering threshold map, see:
+// https://en.wikipedia.org/wiki/Ordered_dithering
+const BAYER_MATRIX_4X4: [[FloatType; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0]
+];
+
+// Tuning values taken from the original FXAA implementation by Timothy Lottes
+const FXAA_REDUCE_MIN: FloatType = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: FloatType = 1.0 / 8.0;
+const FXAA_SPAN_MAX: FloatType = 8.0;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PostProcessError {
+    #[error("Gaussian blur sigma must be finite and greater than 0, got {0}")]
+    InvalidSigma(FloatType)
+}
+
+// A single full-screen pass. In the scene file, a pass is written as an object tagged by its
+// `type`, e.g. `{ "type": "gaussian_blur", "radius": 2, "sigma": 1.0 }`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostProcessPass {
+    BoxBlur { radius: usize },
+    GaussianBlur { radius: usize, sigma: FloatType },
+    Fxaa,
+    // `radius` is the normalized distance from the center (1.0 being the corners) where the
+    // darkening starts. `strength` is how dark the corners end up.
+    Vignette { strength: FloatType, radius: FloatType },
+    Grayscale,
+    OrderedDither { levels: u8 },
+    EdgeDetect { threshold: FloatType }
+}
+
+// Passes are applied in the order they are pushed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PostProcessChain {
+    passes: Vec<PostProcessPass>
+}
+
+impl PostProcessChain {
+    pub fn new(passes: Vec<PostProcessPass>) -> Result<Self, PostProcessError> {
+        for pass in &passes {
+            pass.validate()?;
+        }
+
+        Ok(Self { passes })
+    }
+
+    pub fn push(&mut self, pass: PostProcessPass) -> Result<(), PostProcessError> {
+        pass.validate()?;
+        self.passes.push(pass);
+        Ok(())
+    }
+
+    pub fn passes(&self) -> &[PostProcessPass] {
+        &self.passes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub fn apply(&self, buffer: &mut [u32], width: usize, height: usize) {
+        // The passes sample the image as a full width x height grid, so a buffer that is too
+        // short for those dimensions is left untouched instead of being partially processed
+        let size = width * height;
+        if self.passes.is_empty() || size == 0 || buffer.len() < size {
+            return;
+        }
+
+        let mut image: Vec<Pixel> = buffer[..size].iter()
+            .map(|color| unpack_color(*color))
+            .collect();
+
+        for pass in &self.passes {
+            image = pass.apply(&image, width, height);
+        }
+
+        for (pixel, color) in buffer[..size].iter_mut().zip(image) {
+            *pixel = pack_color(color);
+        }
+    }
+}
+
+impl PostProcessPass {
+    // A sigma of 0 would turn the whole gaussian kernel into NaN, and the frame into black
+    fn validate(&self) -> Result<(), PostProcessError> {
+        match *self {
+            Self::GaussianBlur { sigma, .. } if !(sigma.is_finite() && sigma > 0.0) => {
+                Err(PostProcessError::InvalidSigma(sigma))
+            },
+            _ => Ok(())
+        }
+    }
+
+    fn apply(&self, image: &[Pixel], width: usize, height: usize) -> Vec<Pixel> {
+        match *self {
+            Self::BoxBlur { radius } => {
+                let kernel = vec![1.0; 2 * radius + 1];
+                separable_blur(image, width, height, &kernel)
+            },
+            Self::GaussianBlur { radius, sigma } => {
+                let kernel = gaussian_kernel(radius, sigma);
+                separable_blur(image, width, height, &kernel)
+            },
+            Self::Fxaa => fxaa(image, width, height),
+            Self::Vignette { strength, radius } => vignette(image, width, height, strength, radius),
+            Self::Grayscale => image.iter()
+                .map(|pixel| { let l = luma(*pixel); [l, l, l] })
+                .collect(),
+            Self::OrderedDither { levels } => ordered_dither(image, width, levels),
+            Self::EdgeDetect { threshold } => edge_detect(image, width, height, threshold)
+        }
+    }
+}
+
+fn unpack_color(color: u32) -> Pixel {
+    let [r, g, b] = Color::from_u32(color).rgb();
+    [r as FloatType / 255.0, g as FloatType / 255.0, b as FloatType / 255.0]
+}
+
+fn pack_color(pixel: Pixel) -> u32 {
+    let [r, g, b] = pixel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color::from_rgb(r, g, b).u32_color()
+}
+
+// Rec. 601 luma coefficients
+fn luma(pixel: Pixel) -> FloatType {
+    0.299 * pixel[0] + 0.587 * pixel[1] + 0.114 * pixel[2]
+}
+
+// Pixels outside of the image are clamped to the nearest edge
+fn fetch(image: &[Pixel], width: usize, height: usize, x: isize, y: isize) -> Pixel {
+    let x = x.clamp(0, width as isize - 1) as usize;
+    let y = y.clamp(0, height as isize - 1) as usize;
+    image[x + y * width]
+}
+
+fn sample_bilinear(image: &[Pixel], width: usize, height: usize, x: FloatType, y: FloatType) -> Pixel {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+    let (x0, y0) = (x0 as isize, y0 as isize);
+
+    let top_left = fetch(image, width, height, x0, y0);
+    let top_right = fetch(image, width, height, x0 + 1, y0);
+    let bottom_left = fetch(image, width, height, x0, y0 + 1);
+    let bottom_right = fetch(image, width, height, x0 + 1, y0 + 1);
+
+    let mut result = [0.0; 3];
+    for c in 0..3 {
+        let top = top_left[c] + (top_right[c] - top_left[c]) * tx;
+        let bottom = bottom_left[c] + (bottom_right[c] - bottom_left[c]) * tx;
+        result[c] = top + (bottom - top) * ty;
+    }
+
+    result
+}
+
+fn gaussian_kernel(radius: usize, sigma: FloatType) -> Vec<FloatType> {
+    let two_sigma_sq = 2.0 * sigma * sigma;
+    (0..=2 * radius)
+        .map(|i| {
+            let offset = i as FloatType - radius as FloatType;
+            (-(offset * offset) / two_sigma_sq).exp()
+        })
+        .collect()
+}
+
+// Both blurs are separable, so a horizontal pass followed by a vertical pass is
+// equivalent to the full 2D kernel. The kernel does not need to be normalized.
+fn separable_blur(image: &[Pixel], width: usize, height: usize, kernel: &[FloatType]) -> Vec<Pixel> {
+    let radius = (kernel.len() / 2) as isize;
+    let kernel_sum: FloatType = kernel.iter().sum();
+
+    let convolve = |source: &[Pixel], dx: isize, dy: isize| -> Vec<Pixel> {
+        let mut output = vec![[0.0; 3]; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                for (i, weight) in kernel.iter().enumerate() {
+                    let offset = i as isize - radius;
+                    let pixel = fetch(source, width, height, x as isize + offset * dx, y as isize + offset * dy);
+                    for c in 0..3 {
+                        sum[c] += pixel[c] * weight;
+                    }
+                }
+
+                output[x + y * width] = sum.map(|c| c / kernel_sum);
+            }
+        }
+
+        output
+    };
+
+    let horizontal = convolve(image, 1, 0);
+    convolve(&horizontal, 0, 1)
+}
+
+// Based on the FXAA implementation described at:
+// https://blog.simonrodriguez.fr/articles/2016/07/implementing_fxaa.html
+fn fxaa(image: &[Pixel], width: usize, height: usize) -> Vec<Pixel> {
+    let mut output = image.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as isize, y as isize);
+            let luma_m = luma(image[x + y * width]);
+            let luma_nw = luma(fetch(image, width, height, xi - 1, yi - 1));
+            let luma_ne = luma(fetch(image, width, height, xi + 1, yi - 1));
+            let luma_sw = luma(fetch(image, width, height, xi - 1, yi + 1));
+            let luma_se = luma(fetch(image, width, height, xi + 1, yi + 1));
+
+            let luma_min = luma_m.min(luma_nw.min(luma_ne).min(luma_sw.min(luma_se)));
+            let luma_max = luma_m.max(luma_nw.max(luma_ne).max(luma_sw.max(luma_se)));
+
+            let mut dir_x = -((luma_nw + luma_ne) - (luma_sw + luma_se));
+            let mut dir_y = (luma_nw + luma_sw) - (luma_ne + luma_se);
+
+            let dir_reduce = ((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL)
+                .max(FXAA_REDUCE_MIN);
+            let inverse_dir_min = 1.0 / (dir_x.abs().min(dir_y.abs()) + dir_reduce);
+
+            dir_x = (dir_x * inverse_dir_min).clamp(-FXAA_SPAN_MAX, FXAA_SPAN_MAX);
+            dir_y = (dir_y * inverse_dir_min).clamp(-FXAA_SPAN_MAX, FXAA_SPAN_MAX);
+
+            let sample_along = |t: FloatType| sample_bilinear(
+                image, width, height,
+                x as FloatType + dir_x * t,
+                y as FloatType + dir_y * t
+            );
+
+            let near_1 = sample_along(1.0 / 3.0 - 0.5);
+            let near_2 = sample_along(2.0 / 3.0 - 0.5);
+            let far_1 = sample_along(-0.5);
+            let far_2 = sample_along(0.5);
+
+            let mut rgb_a = [0.0; 3];
+            let mut rgb_b = [0.0; 3];
+            for c in 0..3 {
+                rgb_a[c] = 0.5 * (near_1[c] + near_2[c]);
+                rgb_b[c] = rgb_a[c] * 0.5 + 0.25 * (far_1[c] + far_2[c]);
+            }
+
+            // If the wider sample went past the local contrast range, it crossed another edge
+            let luma_b = luma(rgb_b);
+            output[x + y * width] = if luma_b < luma_min || luma_b > luma_max { rgb_a } else { rgb_b };
+        }
+    }
+
+    output
+}
+
+fn vignette(
+    image: &[Pixel], width: usize, height: usize,
+    strength: FloatType, radius: FloatType
+) -> Vec<Pixel> {
+    let center_x = (width as FloatType - 1.0) * 0.5;
+    let center_y = (height as FloatType - 1.0) * 0.5;
+    let max_distance = (center_x * center_x + center_y * center_y).sqrt().max(FloatType::EPSILON);
+    let falloff = (1.0 - radius).max(FloatType::EPSILON);
+
+    image.iter().enumerate()
+        .map(|(i, pixel)| {
+            let dx = (i % width) as FloatType - center_x;
+            let dy = (i / width) as FloatType - center_y;
+            let distance = (dx * dx + dy * dy).sqrt() / max_distance;
+
+            let t = ((distance - radius) / falloff).clamp(0.0, 1.0);
+            let factor = 1.0 - strength * t * t;
+            pixel.map(|c| c * factor)
+        })
+        .collect()
+}
+
+fn ordered_dither(image: &[Pixel], width: usize, levels: u8) -> Vec<Pixel> {
+    // Anything under 2 levels would not be able to represent any color
+    let steps = levels.max(2) as FloatType - 1.0;
+
+    image.iter().enumerate()
+        .map(|(i, pixel)| {
+            let threshold = BAYER_MATRIX_4X4[(i / width) % 4][(i % width) % 4] / 16.0 - 0.5;
+            pixel.map(|c| ((c * steps + threshold).round() / steps).clamp(0.0, 1.0))
+        })
+        .collect()
+}
+
+// Sobel operator on the luma channel. Edges are drawn white on black.
+fn edge_detect(image: &[Pixel], width: usize, height: usize, threshold: FloatType) -> Vec<Pixel> {
+    let lumas: Vec<Pixel> = image.iter().map(|pixel| [luma(*pixel); 3]).collect();
+    let l = |x: isize, y: isize| fetch(&lumas, width, height, x, y)[0];
+
+    let mut output = vec![[0.0; 3]; width * height];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let gx = (l(x + 1, y - 1) + 2.0 * l(x + 1, y) + l(x + 1, y + 1))
+                - (l(x - 1, y - 1) + 2.0 * l(x - 1, y) + l(x - 1, y + 1));
+            let gy = (l(x - 1, y + 1) + 2.0 * l(x, y + 1) + l(x + 1, y + 1))
+                - (l(x - 1, y - 1) + 2.0 * l(x, y - 1) + l(x + 1, y - 1));
+
+            if (gx * gx + gy * gy).sqrt() >= threshold {
+                output[x as usize + y as usize * width] = [1.0; 3];
+            }
+        }
+    }
+
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_WIDTH: usize = 8;
+    const TEST_HEIGHT: usize = 6;
+    const TEST_SIZE: usize = TEST_WIDTH * TEST_HEIGHT;
+
+    // Left half is white, right half is black
+    fn split_buffer() -> [u32; TEST_SIZE] {
+        let mut buffer = [Color::BLACK.u32_color(); TEST_SIZE];
+        for (i, pixel) in buffer.iter_mut().enumerate() {
+            if i % TEST_WIDTH < TEST_WIDTH / 2 {
+                *pixel = Color::WHITE.u32_color();
+            }
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn test_empty_chain_keeps_buffer() {
+        let mut buffer = split_buffer();
+        PostProcessChain::default().apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        assert_eq!(buffer, split_buffer());
+    }
+
+    #[test]
+    fn test_short_buffer_is_ignored() {
+        let mut buffer = [Color::RED.u32_color(); TEST_SIZE - 1];
+        PostProcessChain::new(vec![PostProcessPass::Grayscale]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        assert!(buffer.iter().all(|pixel| *pixel == Color::RED.u32_color()));
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let mut buffer = [Color::RED.u32_color(); TEST_SIZE];
+        PostProcessChain::new(vec![PostProcessPass::Grayscale]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        // 0.299 * 255 = 76.245
+        assert!(buffer.iter().all(|pixel| *pixel == Color::from_rgb(76, 76, 76).u32_color()));
+    }
+
+    #[test]
+    fn test_blur_keeps_flat_color() {
+        let flat = Color::from_rgb(10, 200, 30).u32_color();
+        let mut buffer = [flat; TEST_SIZE];
+        PostProcessChain::new(vec![
+            PostProcessPass::BoxBlur { radius: 2 },
+            PostProcessPass::GaussianBlur { radius: 2, sigma: 1.0 }
+        ]).unwrap().apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        assert!(buffer.iter().all(|pixel| *pixel == flat));
+    }
+
+    #[test]
+    fn test_box_blur_softens_edge() {
+        let mut buffer = split_buffer();
+        PostProcessChain::new(vec![PostProcessPass::BoxBlur { radius: 1 }]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        // The pixels right next to the boundary are mixed, the far ones are untouched
+        assert_eq!(buffer[0], Color::WHITE.u32_color());
+        assert_eq!(buffer[3], Color::from_rgb(170, 170, 170).u32_color());
+        assert_eq!(buffer[4], Color::from_rgb(85, 85, 85).u32_color());
+        assert_eq!(buffer[7], Color::BLACK.u32_color());
+    }
+
+    #[test]
+    fn test_edge_detect() {
+        let mut buffer = split_buffer();
+        PostProcessChain::new(vec![PostProcessPass::EdgeDetect { threshold: 0.5 }]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        for (i, pixel) in buffer.iter().enumerate() {
+            let x = i % TEST_WIDTH;
+            let expected = if x == 3 || x == 4 { Color::WHITE } else { Color::BLACK };
+            assert_eq!(*pixel, expected.u32_color());
+        }
+    }
+
+    #[test]
+    fn test_vignette_darkens_corners_only() {
+        let mut buffer = [Color::WHITE.u32_color(); TEST_SIZE];
+        PostProcessChain::new(vec![PostProcessPass::Vignette { strength: 1.0, radius: 0.5 }]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        assert_eq!(buffer[0], Color::BLACK.u32_color());
+        assert_eq!(buffer[TEST_SIZE - 1], Color::BLACK.u32_color());
+        assert_eq!(buffer[3 + 2 * TEST_WIDTH], Color::WHITE.u32_color());
+    }
+
+    #[test]
+    fn test_ordered_dither_quantizes() {
+        let mut buffer = [Color::from_rgb(128, 128, 128).u32_color(); TEST_SIZE];
+        PostProcessChain::new(vec![PostProcessPass::OrderedDither { levels: 2 }]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        let white_count = buffer.iter().filter(|p| **p == Color::WHITE.u32_color()).count();
+        let black_count = buffer.iter().filter(|p| **p == Color::BLACK.u32_color()).count();
+        assert_eq!(white_count + black_count, TEST_SIZE);
+        assert_eq!(white_count, TEST_SIZE / 2);
+    }
+
+    #[test]
+    fn test_fxaa_keeps_flat_color() {
+        let flat = Color::from_rgb(90, 40, 220).u32_color();
+        let mut buffer = [flat; TEST_SIZE];
+        PostProcessChain::new(vec![PostProcessPass::Fxaa]).unwrap()
+            .apply(&mut buffer, TEST_WIDTH, TEST_HEIGHT);
+
+        assert!(buffer.iter().all(|pixel| *pixel == flat));
+    }
+
+    #[test]
+    fn test_invalid_gaussian_sigma() {
+        for sigma in [0.0, -1.0, FloatType::NAN] {
+            let pass = PostProcessPass::GaussianBlur { radius: 2, sigma };
+            assert!(matches!(PostProcessChain::new(vec![pass]), Err(PostProcessError::InvalidSigma(_))));
+            assert!(PostProcessChain::default().push(pass).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_pass_list() {
+        let passes: Vec<PostProcessPass> = serde_json::from_str(r#"[
+            { "type": "fxaa" },
+            { "type": "gaussian_blur", "radius": 3, "sigma": 1.5 },
+            { "type": "ordered_dither", "levels": 4 }
+        ]"#).unwrap();
+
+        assert_eq!(passes, vec![
+            PostProcessPass::Fxaa,
+            PostProcessPass::GaussianBlur { radius: 3, sigma: 1.5 },
+            PostProcessPass::OrderedDither { levels: 4 }
+        ]);
+    }
+}
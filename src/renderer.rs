@@ -1,14 +1,18 @@
 use thiserror::Error;
 
-use crate::math_utils::vector::{Vector2, Vector3};
-use crate::math_utils::FloatType;
+use crate::math_utils::vector::{Vector2, Vector3, Vector4};
+use crate::math_utils::{FloatType, perspective_divide};
 use crate::object::Object;
-use crate::camera::Camera;
+use crate::camera::{Camera, DepthMode};
 use crate::transform::Transform;
+use crate::post_process::PostProcessChain;
 
 const VERTEX_SIZE: usize = 13;
 const VERTEX_COLOR: Color = Color::WHITE;
 const EDGE_COLOR: Color = Color::WHITE;
+const FACE_COLOR: Color = Color::WHITE;
+// Faces seen edge-on are never fully dark, so the silhouette stays visible
+const MIN_FACE_BRIGHTNESS: FloatType = 0.2;
 
 #[derive(Clone, Copy)]
 pub struct Color(u32);
@@ -27,9 +31,17 @@ impl Color {
         )
     }
 
+    pub fn from_u32(value: u32) -> Self {
+        Self(value)
+    }
+
     pub fn u32_color(&self) -> u32 {
         self.0
     }
+
+    pub fn rgb(&self) -> [u8; 3] {
+        [(self.0 >> 16) as u8, (self.0 >> 8) as u8, self.0 as u8]
+    }
 }
 
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
@@ -42,7 +54,9 @@ pub enum RenderType {
 #[derive(Debug)]
 pub struct Renderer {
     buffer_width: usize,
-    buffer_height: usize
+    buffer_height: usize,
+    // Only used when rendering faces, cleared at the start of each render
+    depth_buffer: Vec<FloatType>
 }
 
 #[derive(Debug, Error, PartialEq)]
@@ -52,21 +66,49 @@ pub enum RendererError {
 }
 
 impl Renderer {
-    pub fn render(&self, obj: &Object, camera: &Camera, buffer: &mut [u32], render_type: RenderType) -> Result<(), RendererError> {
-        match render_type {
-            RenderType::Vertex => self.vertex_render(obj, camera, buffer),
-            RenderType::Edge => self.edge_render(obj, camera, buffer),
-            RenderType::Face => unimplemented!("Not implemented yet!")
+    // Renders `obj` along with all of its descendants
+    pub fn render(&mut self, obj: &Object, camera: &Camera, buffer: &mut [u32], render_type: RenderType) -> Result<(), RendererError> {
+        // Once per frame, so objects drawn earlier still hide the ones behind them
+        self.clear_depth_buffer(camera.depth_mode());
+        self.render_tree(obj, camera, buffer, render_type)
+    }
+
+    fn render_tree(&mut self, obj: &Object, camera: &Camera, buffer: &mut [u32], render_type: RenderType) -> Result<(), RendererError> {
+        // Skip objects that can't be seen at all. The bounds only cover the object's own mesh,
+        // so children are checked on their own.
+        let is_visible = obj.world_bounding_sphere()
+            .is_none_or(|sphere| camera.is_sphere_visible(&sphere));
+
+        if is_visible {
+            match render_type {
+                RenderType::Vertex => self.vertex_render(obj, camera, buffer)?,
+                RenderType::Edge => self.edge_render(obj, camera, buffer)?,
+                RenderType::Face => self.face_render(obj, camera, buffer)?
+            }
+        }
+
+        for child in &obj.children {
+            self.render_tree(child, camera, buffer, render_type)?;
         }
+
+        Ok(())
+    }
+
+    // Runs the full-screen passes on the already rendered buffer
+    pub fn apply_post_process(&self, chain: &PostProcessChain, buffer: &mut [u32]) {
+        chain.apply(buffer, self.buffer_width, self.buffer_height);
     }
 
     pub fn vertex_render(&self, obj: &Object, camera: &Camera, buffer: &mut [u32]) -> Result<(), RendererError> {
         for vert in &obj.mesh.vertices {
-            let screen_pos = self.obj_space_to_screen_space(*vert, &obj.transform, camera);
+            let clip_pos = self.obj_space_to_clip_space(*vert, &obj.transform, camera);
+            if !is_inside_clip_volume(clip_pos) {
+                continue;
+            }
+
+            let screen_pos = self.clip_space_to_screen_space(clip_pos);
             self.draw_vertex(buffer, screen_pos)?;
         }
-        // Perform rasterization
-        // Draw to buffer
 
         Ok(())
     }
@@ -75,16 +117,23 @@ impl Renderer {
         for face in &obj.mesh.faces {
             for i in 0..3 {
                 let edge = [face[i], face[(i+1) % 3]];
-                let p1 = self.obj_space_to_screen_space(
+                let p1 = self.obj_space_to_clip_space(
                     obj.mesh.vertices[edge[0] as usize],
                     &obj.transform,
                     camera
                 );
-                let p2 = self.obj_space_to_screen_space(
+                let p2 = self.obj_space_to_clip_space(
                     obj.mesh.vertices[edge[1] as usize],
                     &obj.transform,
                     camera
                 );
+
+                let Some((p1, p2)) = clip_line(p1, p2) else {
+                    continue;
+                };
+
+                let p1 = self.clip_space_to_screen_space(p1);
+                let p2 = self.clip_space_to_screen_space(p2);
                 self.bresenham_line(
                     EDGE_COLOR,
                     buffer,
@@ -97,16 +146,118 @@ impl Renderer {
         Ok(())
     }
 
-    fn obj_space_to_screen_space(&self, position: Vector3<FloatType>, obj_transform: &Transform, camera: &Camera) -> Vector2<usize> {
+    // Filled, flat shaded triangles. Brightness depends on how much a face points towards the
+    // camera, and hidden faces are removed with the depth buffer.
+    // Only draws `obj` itself, and expects the depth buffer to be cleared beforehand.
+    pub fn face_render(&mut self, obj: &Object, camera: &Camera, buffer: &mut [u32]) -> Result<(), RendererError> {
+        let depth_mode = camera.depth_mode();
+
+        for face in &obj.mesh.faces {
+            let cam_pos = face.map(|i| {
+                let world_pos = obj.transform.local_to_world(obj.mesh.vertices[i as usize]);
+                camera.transform.world_to_local(world_pos)
+            });
+
+            let normal = (cam_pos[1] - cam_pos[0]).cross(cam_pos[2] - cam_pos[0]).normalized();
+            // The camera sits at the origin of camera space
+            let view_dir = (cam_pos[0] + cam_pos[1] + cam_pos[2]).normalized();
+            let brightness = normal.dot(view_dir).abs().max(MIN_FACE_BRIGHTNESS);
+            let [r, g, b] = FACE_COLOR.rgb()
+                .map(|c| (c as FloatType * brightness).round() as u8);
+            let color = Color::from_rgb(r, g, b);
+
+            let polygon = clip_polygon(cam_pos.map(|p| camera.project_to_clip_space(p)).to_vec());
+            let screen_pos: Vec<Vector3<FloatType>> = polygon.into_iter()
+                .map(|p| self.ncd_space_to_screen_space(perspective_divide(p)))
+                .collect();
+
+            // The clipped polygon is convex, so it can be split into a triangle fan
+            for i in 1..screen_pos.len().saturating_sub(1) {
+                self.fill_triangle(buffer, [screen_pos[0], screen_pos[i], screen_pos[i + 1]], color, depth_mode)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn depth_buffer(&self) -> &[FloatType] {
+        &self.depth_buffer
+    }
+
+    pub fn clear_depth_buffer(&mut self, depth_mode: DepthMode) {
+        self.depth_buffer.fill(depth_mode.far_depth());
+    }
+
+    fn obj_space_to_clip_space(&self, position: Vector3<FloatType>, obj_transform: &Transform, camera: &Camera) -> Vector4<FloatType> {
         let world_pos = obj_transform.local_to_world(position);
         let cam_pos = camera.transform.world_to_local(world_pos);
-        let ncd_pos = camera.project_to_ncd_space(cam_pos);
-        let screen_pos = Vector2::new([
-            (((ncd_pos.x() + 1.0) * 0.5) * self.buffer_width as FloatType) as usize,
-            (((ncd_pos.y() + 1.0) * 0.5) * self.buffer_height as FloatType) as usize
-        ]);
+        camera.project_to_clip_space(cam_pos)
+    }
+
+    // Only valid for points that are inside of the clip volume
+    fn clip_space_to_screen_space(&self, clip_pos: Vector4<FloatType>) -> Vector2<usize> {
+        let ncd_pos = perspective_divide(clip_pos);
+        // NDC of exactly 1.0 would land one pixel outside of the buffer
+        let max_x = self.buffer_width.saturating_sub(1);
+        let max_y = self.buffer_height.saturating_sub(1);
+        Vector2::new([
+            ((((ncd_pos.x() + 1.0) * 0.5) * self.buffer_width as FloatType) as usize).min(max_x),
+            ((((ncd_pos.y() + 1.0) * 0.5) * self.buffer_height as FloatType) as usize).min(max_y)
+        ])
+    }
 
-        screen_pos
+    // x and y are in pixels, z keeps the NDC depth
+    fn ncd_space_to_screen_space(&self, ncd_pos: Vector3<FloatType>) -> Vector3<FloatType> {
+        Vector3::new([
+            ((ncd_pos.x() + 1.0) * 0.5) * self.buffer_width as FloatType,
+            ((ncd_pos.y() + 1.0) * 0.5) * self.buffer_height as FloatType,
+            ncd_pos.z()
+        ])
+    }
+
+    // Rasterize using edge functions, sampling at pixel centers. Depth is interpolated linearly,
+    // which is correct since NDC depth is affine in screen space.
+    fn fill_triangle(
+        &mut self, buffer: &mut [u32],
+        vertices: [Vector3<FloatType>; 3],
+        color: Color, depth_mode: DepthMode
+    ) -> Result<(), RendererError> {
+        let [a, b, c] = vertices;
+        let area = edge_function(a, b, c);
+        if area == 0.0 {
+            return Ok(());
+        }
+
+        let max_x = self.buffer_width.saturating_sub(1) as FloatType;
+        let max_y = self.buffer_height.saturating_sub(1) as FloatType;
+        let min_x = a.x().min(b.x()).min(c.x()).floor().clamp(0.0, max_x) as usize;
+        let min_y = a.y().min(b.y()).min(c.y()).floor().clamp(0.0, max_y) as usize;
+        let end_x = a.x().max(b.x()).max(c.x()).ceil().clamp(0.0, max_x) as usize;
+        let end_y = a.y().max(b.y()).max(c.y()).ceil().clamp(0.0, max_y) as usize;
+
+        for y in min_y..=end_y {
+            for x in min_x..=end_x {
+                let p = Vector3::new([x as FloatType + 0.5, y as FloatType + 0.5, 0.0]);
+                // Dividing by the area makes these work for either winding order
+                let w_a = edge_function(b, c, p) / area;
+                let w_b = edge_function(c, a, p) / area;
+                let w_c = edge_function(a, b, p) / area;
+                if w_a < 0.0 || w_b < 0.0 || w_c < 0.0 {
+                    continue;
+                }
+
+                let depth = w_a * a.z() + w_b * b.z() + w_c * c.z();
+                let index = x + y * self.buffer_width;
+                if !depth_mode.is_closer(depth, self.depth_buffer[index]) {
+                    continue;
+                }
+
+                self.depth_buffer[index] = depth;
+                self.draw_pixel(buffer, Vector2::new([x, y]), color)?;
+            }
+        }
+
+        Ok(())
     }
 
     // Draw a square of `SIDE_LENGTH` centered at `center`
@@ -118,11 +269,15 @@ impl Renderer {
             center.y().saturating_sub(HALF_SIDE)
         ]);
 
-        for x in 0..=2*HALF_SIDE {
-            for y in 0..=2*HALF_SIDE {
+        // Parts of the square that fall outside of the buffer are cut off
+        let end_x = (center.x() + HALF_SIDE).min(self.buffer_width.saturating_sub(1));
+        let end_y = (center.y() + HALF_SIDE).min(self.buffer_height.saturating_sub(1));
+
+        for x in start.x()..=end_x {
+            for y in start.y()..=end_y {
                 self.draw_pixel(
                     buffer,
-                    start + Vector2::<usize>::new([x, y]),
+                    Vector2::<usize>::new([x, y]),
                     VERTEX_COLOR
                 )?;
             }
@@ -148,7 +303,8 @@ impl Renderer {
         let sy = if curr_y < end_y {1} else {-1};
 
         loop {
-            self.draw_pixel(buffer, Vector2::new([curr_x as usize, curr_y as usize]), color);
+            // Lines are allowed to go out of the buffer, those pixels are simply not drawn
+            let _ = self.draw_pixel(buffer, Vector2::new([curr_x as usize, curr_y as usize]), color);
             if curr_x == end_x && curr_y == end_y {break}
             let e2 = error * 2;
 
@@ -180,13 +336,94 @@ impl Renderer {
     pub fn new() -> Self {
         Self {
             buffer_width: 0,
-            buffer_height: 0
+            buffer_height: 0,
+            depth_buffer: vec![]
         }
     }
 
     pub fn update_buffer_size(&mut self, width: usize, height: usize) {
         self.buffer_width = width;
         self.buffer_height = height;
+        self.depth_buffer.resize(width * height, 0.0);
+    }
+
+    // Cameras rendering to this buffer should use this, so the image is not stretched
+    pub fn aspect_ratio(&self) -> FloatType {
+        self.buffer_width as FloatType / self.buffer_height as FloatType
+    }
+}
+
+// The visible volume in clip space is -w <= x, y <= w and 0 <= z <= w
+fn is_inside_clip_volume(clip_pos: Vector4<FloatType>) -> bool {
+    clip_plane_distances(clip_pos).iter().all(|d| *d >= 0.0)
+}
+
+// Signed distances to the 6 clipping planes, positive means inside
+fn clip_plane_distances(clip_pos: Vector4<FloatType>) -> [FloatType; 6] {
+    let [x, y, z, w] = *clip_pos.array();
+    [w + x, w - x, w + y, w - y, z, w - z]
+}
+
+// Liang-Barsky line clipping, done in homogeneous clip space so that lines that go behind the
+// camera are handled correctly. Returns `None` if no part of the line is visible.
+fn clip_line(p1: Vector4<FloatType>, p2: Vector4<FloatType>) -> Option<(Vector4<FloatType>, Vector4<FloatType>)> {
+    let d1 = clip_plane_distances(p1);
+    let d2 = clip_plane_distances(p2);
+
+    let mut t_start: FloatType = 0.0;
+    let mut t_end: FloatType = 1.0;
+
+    for i in 0..6 {
+        match (d1[i] < 0.0, d2[i] < 0.0) {
+            (true, true) => return None,
+            (true, false) => t_start = t_start.max(d1[i] / (d1[i] - d2[i])),
+            (false, true) => t_end = t_end.min(d1[i] / (d1[i] - d2[i])),
+            (false, false) => ()
+        }
+    }
+
+    if t_start > t_end {
+        return None;
+    }
+
+    Some((lerp_clip_space(p1, p2, t_start), lerp_clip_space(p1, p2, t_end)))
+}
+
+// Sutherland-Hodgman polygon clipping against all 6 planes of the clip volume
+fn clip_polygon(mut polygon: Vec<Vector4<FloatType>>) -> Vec<Vector4<FloatType>> {
+    for plane in 0..6 {
+        let input = std::mem::take(&mut polygon);
+        for i in 0..input.len() {
+            let current = input[i];
+            let next = input[(i + 1) % input.len()];
+            let d_current = clip_plane_distances(current)[plane];
+            let d_next = clip_plane_distances(next)[plane];
+
+            if d_current >= 0.0 {
+                polygon.push(current);
+            }
+            // The edge crosses the plane
+            if (d_current >= 0.0) != (d_next >= 0.0) {
+                polygon.push(lerp_clip_space(current, next, d_current / (d_current - d_next)));
+            }
+        }
+    }
+
+    polygon
+}
+
+fn lerp_clip_space(p1: Vector4<FloatType>, p2: Vector4<FloatType>, t: FloatType) -> Vector4<FloatType> {
+    p1.lerp(p2, t)
+}
+
+// Twice the signed area of triangle (a, b, p), only x and y are used
+fn edge_function(a: Vector3<FloatType>, b: Vector3<FloatType>, p: Vector3<FloatType>) -> FloatType {
+    (b.x() - a.x()) * (p.y() - a.y()) - (b.y() - a.y()) * (p.x() - a.x())
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -196,7 +433,7 @@ pub mod tests {
 
     use super::*;
     const TEST_BUFFER_WIDTH: usize = 100;
-    const TEST_BUFFER_HEIGHT: usize = 50;
+    const TEST_BUFFER_HEIGHT: usize = 100;
     const TEST_BUFFER_SIZE: usize = TEST_BUFFER_WIDTH * TEST_BUFFER_HEIGHT;
 
     fn init_renderer_and_buffer() -> (Renderer, [u32; TEST_BUFFER_SIZE]) { 
@@ -252,4 +489,89 @@ pub mod tests {
 
         assert_eq!(buffer, correct_buffer);
     }
+
+    #[test]
+    fn test_draw_vertex_on_buffer_edge() {
+        let (renderer, mut buffer) = init_renderer_and_buffer();
+        renderer.draw_vertex(&mut buffer, Vector2::new([TEST_BUFFER_WIDTH - 1, TEST_BUFFER_HEIGHT - 1])).unwrap();
+
+        assert_eq!(buffer[TEST_BUFFER_SIZE - 1], VERTEX_COLOR.u32_color());
+    }
+
+    #[test]
+    fn test_fill_triangle_depth_test() {
+        let (mut renderer, mut buffer) = init_renderer_and_buffer();
+        let depth_mode = DepthMode::default();
+        renderer.depth_buffer.fill(depth_mode.far_depth());
+
+        let triangle = |depth| [
+            Vector3::new([0.0, 0.0, depth]),
+            Vector3::new([20.0, 0.0, depth]),
+            Vector3::new([0.0, 20.0, depth])
+        ];
+
+        renderer.fill_triangle(&mut buffer, triangle(0.25), Color::RED, depth_mode).unwrap();
+        // Behind the first triangle, should not be drawn
+        renderer.fill_triangle(&mut buffer, triangle(0.5), Color::GREEN, depth_mode).unwrap();
+
+        assert_eq!(buffer[5 + 5 * TEST_BUFFER_WIDTH], Color::RED.u32_color());
+        assert_eq!(renderer.depth_buffer[5 + 5 * TEST_BUFFER_WIDTH], 0.25);
+        // Outside of the triangle
+        assert_eq!(buffer[15 + 15 * TEST_BUFFER_WIDTH], 0);
+        assert_eq!(renderer.depth_buffer[15 + 15 * TEST_BUFFER_WIDTH], 1.0);
+
+        let reversed = DepthMode { reversed_z: true, infinite_far_plane: false };
+        renderer.depth_buffer.fill(reversed.far_depth());
+        renderer.fill_triangle(&mut buffer, triangle(0.25), Color::RED, reversed).unwrap();
+        renderer.fill_triangle(&mut buffer, triangle(0.5), Color::GREEN, reversed).unwrap();
+
+        assert_eq!(buffer[5 + 5 * TEST_BUFFER_WIDTH], Color::GREEN.u32_color());
+    }
+
+    #[test]
+    fn test_clip_polygon() {
+        let inside = vec![
+            Vector4::new([0.0, 0.0, 0.5, 1.0]),
+            Vector4::new([0.5, 0.0, 0.5, 1.0]),
+            Vector4::new([0.0, 0.5, 0.5, 1.0])
+        ];
+        assert_eq!(clip_polygon(inside.clone()), inside);
+
+        // One corner sticks out to the right, turning the triangle into a quad
+        let clipped = clip_polygon(vec![
+            Vector4::new([0.0, 0.0, 0.5, 1.0]),
+            Vector4::new([2.0, 0.0, 0.5, 1.0]),
+            Vector4::new([0.0, 0.5, 0.5, 1.0])
+        ]);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|p| is_inside_clip_volume(*p)));
+    }
+
+    #[test]
+    fn test_clip_line_fully_inside() {
+        let p1 = Vector4::new([0.0, 0.0, 0.5, 1.0]);
+        let p2 = Vector4::new([0.5, -0.5, 1.0, 2.0]);
+
+        assert_eq!(clip_line(p1, p2), Some((p1, p2)));
+    }
+
+    #[test]
+    fn test_clip_line_fully_outside() {
+        // Both points are to the right of the view volume
+        let p1 = Vector4::new([2.0, 0.0, 0.5, 1.0]);
+        let p2 = Vector4::new([3.0, 0.0, 0.5, 1.0]);
+
+        assert_eq!(clip_line(p1, p2), None);
+    }
+
+    #[test]
+    fn test_clip_line_behind_camera() {
+        // Second point is behind the near plane
+        let p1 = Vector4::new([0.0, 0.0, 1.0, 2.0]);
+        let p2 = Vector4::new([0.0, 0.0, -1.0, 2.0]);
+
+        let (start, end) = clip_line(p1, p2).unwrap();
+        assert_eq!(start, p1);
+        assert_eq!(end, Vector4::new([0.0, 0.0, 0.0, 2.0]));
+    }
 }
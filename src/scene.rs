@@ -4,28 +4,394 @@ use std::fs;
 use crate::math_utils::vector::Vector3;
 use crate::object::Object;
 use crate::math_utils::FloatType;
+use crate::post_process::{PostProcessChain, PostProcessPass};
+use crate::controller::OrbitController;
+use crate::camera_path::{CameraKeyframe, CameraPath, CameraPathError};
+use crate::math_utils::quaternion::Quaternion;
+use crate::camera::{Camera, CameraError, DepthMode, FovMode, ProjectionType};
+use crate::animation::{AnimationError, Interpolation, Keyframe, LoopMode, Track, TransformAnimation};
 
 
 pub struct Scene {
     pub object: Object,
+    pub camera: Camera,
+    pub post_process: PostProcessChain,
+    pub orbit: Option<OrbitController>,
+    pub camera_path: Option<CameraPath>
 }
 
+// Angles are in degrees here, to keep the scene file readable
 #[derive(Serialize, Deserialize)]
-struct SceneConfig<'a> {
-    filename: &'a str,
-    position: [FloatType; 3]
+struct OrbitConfig {
+    target: [FloatType; 3],
+    yaw: FloatType,
+    pitch: FloatType,
+    distance: FloatType,
+    // Degrees per second
+    #[serde(default)]
+    yaw_speed: FloatType
+}
+
+impl From<OrbitConfig> for OrbitController {
+    fn from(config: OrbitConfig) -> Self {
+        OrbitController::new(
+            Vector3::new(config.target),
+            config.yaw.to_radians(),
+            config.pitch.to_radians(),
+            config.distance
+        ).with_yaw_speed(config.yaw_speed.to_radians())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CameraKeyframeConfig {
+    // Seconds
+    time: FloatType,
+    position: [FloatType; 3],
+    // Euler angles in degrees
+    #[serde(default)]
+    rotation: [FloatType; 3]
+}
+
+#[derive(Serialize, Deserialize)]
+struct CameraPathConfig {
+    keyframes: Vec<CameraKeyframeConfig>,
+    #[serde(default)]
+    looping: bool
+}
+
+impl TryFrom<CameraPathConfig> for CameraPath {
+    type Error = CameraPathError;
+
+    fn try_from(config: CameraPathConfig) -> Result<Self, Self::Error> {
+        let keyframes = config.keyframes.into_iter()
+            .map(|keyframe| {
+                let [x, y, z] = keyframe.rotation.map(FloatType::to_radians);
+                CameraKeyframe::new(
+                    keyframe.time,
+                    Vector3::new(keyframe.position),
+                    Quaternion::from_euler_angles(x, y, z)
+                )
+            })
+            .collect();
+
+        CameraPath::new(keyframes, config.looping)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyframeConfig {
+    // Seconds
+    time: FloatType,
+    value: [FloatType; 3]
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackConfig {
+    #[serde(default)]
+    interpolation: Interpolation,
+    keyframes: Vec<KeyframeConfig>
+}
+
+impl TrackConfig {
+    fn into_vector_track(self) -> Result<Track<Vector3<FloatType>>, AnimationError> {
+        let keyframes = self.keyframes.into_iter()
+            .map(|keyframe| Keyframe::new(keyframe.time, Vector3::new(keyframe.value)))
+            .collect();
+
+        Track::new(keyframes, self.interpolation)
+    }
+
+    // Values are Euler angles in degrees
+    fn into_rotation_track(self) -> Result<Track<Quaternion>, AnimationError> {
+        let keyframes = self.keyframes.into_iter()
+            .map(|keyframe| {
+                let [x, y, z] = keyframe.value.map(FloatType::to_radians);
+                Keyframe::new(keyframe.time, Quaternion::from_euler_angles(x, y, z))
+            })
+            .collect();
+
+        Track::new(keyframes, self.interpolation)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnimationConfig {
+    #[serde(default)]
+    loop_mode: LoopMode,
+    #[serde(default)]
+    position: Option<TrackConfig>,
+    #[serde(default)]
+    rotation: Option<TrackConfig>,
+    #[serde(default)]
+    scale: Option<TrackConfig>
+}
+
+impl TryFrom<AnimationConfig> for TransformAnimation {
+    type Error = AnimationError;
+
+    fn try_from(config: AnimationConfig) -> Result<Self, Self::Error> {
+        Ok(TransformAnimation {
+            position: config.position.map(TrackConfig::into_vector_track).transpose()?,
+            rotation: config.rotation.map(TrackConfig::into_rotation_track).transpose()?,
+            scale: config.scale.map(TrackConfig::into_vector_track).transpose()?,
+            loop_mode: config.loop_mode
+        })
+    }
+}
+
+// A node of the scene graph. The root's transform is in world space, and every other node's
+// is relative to its parent.
+// Without a filename, the object has no mesh and only serves as a pivot for its children.
+#[derive(Serialize, Deserialize)]
+struct ObjectConfig {
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    position: [FloatType; 3],
+    // Euler angles in degrees
+    #[serde(default)]
+    rotation: [FloatType; 3],
+    #[serde(default = "default_scale")]
+    scale: [FloatType; 3],
+
+    #[serde(default)]
+    animation: Option<AnimationConfig>,
+
+    #[serde(default)]
+    children: Vec<ObjectConfig>
+}
+
+fn default_scale() -> [FloatType; 3] {
+    [1.0; 3]
+}
+
+impl ObjectConfig {
+    fn into_object(self) -> Object {
+        let mut object = match &self.filename {
+            Some(filename) => Object::new(filename).unwrap(),
+            None => Object::default()
+        };
+
+        let [x, y, z] = self.rotation.map(FloatType::to_radians);
+        object.transform.set_position(Vector3::new(self.position));
+        object.transform.set_rotation(Quaternion::from_euler_angles(x, y, z));
+        object.transform.set_scale(Vector3::new(self.scale));
+        object.animation = self.animation.map(|animation| TransformAnimation::try_from(animation).unwrap());
+        object.children = self.children.into_iter().map(ObjectConfig::into_object).collect();
+
+        object
+    }
+}
+
+// Every field is optional; missing ones fall back to the values below
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct CameraConfig {
+    position: [FloatType; 3],
+    // Euler angles in degrees, ignored when `look_at` is set
+    rotation: [FloatType; 3],
+    look_at: Option<[FloatType; 3]>,
+    up: [FloatType; 3],
+
+    // Degrees
+    fov: FloatType,
+    fov_mode: FovMode,
+    near: FloatType,
+    far: FloatType,
+    projection: ProjectionType,
+    // Only used by orthographic projection
+    view_height: Option<FloatType>,
+    depth_mode: DepthMode
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            look_at: None,
+            up: [0.0, 1.0, 0.0],
+
+            fov: 60.0,
+            fov_mode: FovMode::Vertical,
+            near: 1.0,
+            far: 100.0,
+            projection: ProjectionType::Perspective,
+            view_height: None,
+            depth_mode: DepthMode::default()
+        }
+    }
+}
+
+impl TryFrom<CameraConfig> for Camera {
+    type Error = CameraError;
+
+    fn try_from(config: CameraConfig) -> Result<Self, Self::Error> {
+        let mut camera = Camera::new(config.near, config.far, config.fov);
+        // `Camera::new` takes the values as they are, the setters make sure they are valid
+        camera.set_clip_planes(config.near, config.far)?;
+        camera.set_fov_angle(config.fov)?;
+        if let Some(view_height) = config.view_height {
+            camera.set_orthographic_view_height(view_height)?;
+        }
+        camera.set_fov_mode(config.fov_mode);
+        camera.set_projection_type(config.projection);
+        camera.set_depth_mode(config.depth_mode);
+
+        camera.transform.set_position(Vector3::new(config.position));
+        match config.look_at {
+            Some(target) => camera.look_at(Vector3::new(target), Vector3::new(config.up)),
+            None => {
+                let [x, y, z] = config.rotation.map(FloatType::to_radians);
+                camera.transform.set_rotation(Quaternion::from_euler_angles(x, y, z));
+                camera.transform.update();
+            }
+        }
+
+        Ok(camera)
+    }
+}
+
+// The root object's fields sit at the top level of the scene file, next to the camera
+#[derive(Serialize, Deserialize)]
+struct SceneConfig {
+    #[serde(flatten)]
+    object: ObjectConfig,
+
+    #[serde(default)]
+    camera: CameraConfig,
+
+    #[serde(default)]
+    post_process: Vec<PostProcessPass>,
+
+    #[serde(default)]
+    orbit: Option<OrbitConfig>,
+
+    #[serde(default)]
+    camera_path: Option<CameraPathConfig>
 }
 
 impl Scene {
     pub fn new(filename: &str) -> Self {
         let contents = fs::read_to_string(filename).unwrap();
         let scene_config: SceneConfig = serde_json::from_str(&contents).unwrap();
-        let mut object = Object::new(scene_config.filename).unwrap();
-        object.transform.set_position(Vector3::<FloatType>::new(scene_config.position));
-        object.transform.update();
+        let mut object = scene_config.object.into_object();
+        object.animate(0.0);
+        object.update_transforms();
 
-        Scene {
-            object
+        let mut scene = Scene {
+            object,
+            camera: Camera::try_from(scene_config.camera).unwrap(),
+            post_process: PostProcessChain::new(scene_config.post_process).unwrap(),
+            orbit: scene_config.orbit.map(OrbitController::from),
+            camera_path: scene_config.camera_path.map(|path| CameraPath::try_from(path).unwrap())
+        };
+
+        // Orbit and camera path drive the camera, so they override its starting position
+        if let Some(path) = &scene.camera_path {
+            path.apply(&mut scene.camera, path.start_time());
+        }
+        else if let Some(orbit) = &scene.orbit {
+            orbit.apply(&mut scene.camera);
         }
+
+        scene
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use float_cmp::ApproxEq;
+    use crate::math_utils::vector::{vector, Vector};
+    use crate::math_utils::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    #[test]
+    fn test_camera_config_defaults() {
+        let config: CameraConfig = serde_json::from_str("{}").unwrap();
+        let camera = Camera::try_from(config).unwrap();
+
+        assert_eq!(camera.near_plane_distance(), 1.0);
+        assert_eq!(camera.far_plane_distance(), 100.0);
+        assert_eq!(camera.fov_angle(), 60.0);
+        assert_eq!(camera.projection_type(), ProjectionType::Perspective);
+        assert_eq!(camera.transform.position(), vector![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_camera_config_look_at() {
+        let config: CameraConfig = serde_json::from_str(r#"{
+            "position": [0.0, 0.0, -10.0],
+            "look_at": [3.0, 0.0, -6.0],
+            "fov": 75.0,
+            "near": 0.5,
+            "far": 50.0,
+            "projection": "orthographic",
+            "depth_mode": { "reversed_z": true }
+        }"#).unwrap();
+        let camera = Camera::try_from(config).unwrap();
+
+        assert_eq!(camera.fov_angle(), 75.0);
+        assert_eq!(camera.far_plane_distance(), 50.0);
+        assert_eq!(camera.projection_type(), ProjectionType::Orthographic);
+        assert!(camera.depth_mode().reversed_z);
+
+        let target_in_camera = camera.transform.world_to_local(vector![3.0, 0.0, -6.0]);
+        assert!(target_in_camera.approx_eq(vector![0.0, 0.0, 5.0], (1e-4, 4)));
+    }
+
+    #[test]
+    fn test_animation_config() {
+        let config: AnimationConfig = serde_json::from_str(r#"{
+            "loop_mode": "ping_pong",
+            "rotation": {
+                "interpolation": "slerp",
+                "keyframes": [{ "time": 0.0, "value": [0.0, 0.0, 0.0] }, { "time": 2.0, "value": [0.0, 90.0, 0.0] }]
+            }
+        }"#).unwrap();
+        let animation = TransformAnimation::try_from(config).unwrap();
+
+        assert_eq!(animation.loop_mode, LoopMode::PingPong);
+        assert_eq!(animation.duration(), 2.0);
+        assert!(animation.position.is_none());
+        assert!(animation.rotation.unwrap().sample(1.0).approx_eq(
+            Quaternion::from_euler_angles(0.0, FRAC_PI_4, 0.0),
+            (1e-6, 4)
+        ));
+    }
+
+    #[test]
+    fn test_root_object_config_matches_children() {
+        let config: SceneConfig = serde_json::from_str(r#"{
+            "position": [1.0, 2.0, 3.0],
+            "rotation": [0.0, 90.0, 0.0],
+            "scale": [2.0, 2.0, 2.0],
+            "children": [{ "position": [1.0, 0.0, 0.0], "scale": [0.5, 0.5, 0.5] }],
+            "camera": { "fov": 75.0 }
+        }"#).unwrap();
+        let mut object = config.object.into_object();
+        object.update_transforms();
+
+        assert_eq!(object.transform.scale(), vector![2.0, 2.0, 2.0]);
+        assert!(object.transform.rotation().approx_eq(
+            Quaternion::from_euler_angles(0.0, FRAC_PI_2, 0.0),
+            (1e-6, 4)
+        ));
+        // The child's +x offset is rotated onto -z and doubled by its parent
+        let child_position = object.children[0].transform.local_to_world(vector![0.0, 0.0, 0.0]);
+        assert!(child_position.approx_eq(vector![1.0, 2.0, 1.0], (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_invalid_camera_config() {
+        let config: CameraConfig = serde_json::from_str(r#"{ "near": 10.0, "far": 5.0 }"#).unwrap();
+
+        assert_eq!(
+            Camera::try_from(config).err(),
+            Some(CameraError::InvalidFarPlane { near: 10.0, far: 5.0 })
+        );
     }
 }
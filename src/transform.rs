@@ -1,17 +1,27 @@
 use std::default::Default;
 
 use crate::math_utils;
-use math_utils::{FloatType, transform_3d_point};
-use math_utils::matrix::Matrix4;
+use math_utils::{FloatType, MathError, transform_3d_point, transform_3d_direction};
+use math_utils::matrix::{Matrix3, Matrix4};
 use math_utils::quaternion::Quaternion;
 use math_utils::vector::Vector3;
 
 
-// Any operation that impacts the field `matrix` must set the dirty flag
+// Any operation that impacts the field `matrix` must set the dirty flag.
+// Position, rotation and scale are relative to the parent, if there is one. `matrix` maps local
+// space into the parent's space, while `world_matrix` goes all the way to world space.
 #[derive(Debug)]
 pub struct Transform {
     matrix: Matrix4,
     inverse_matrix: Matrix4,
+    // Inverse-transpose of the upper-left 3x3 part of the world matrix, for normals
+    normal_matrix: Matrix3,
+
+    // Parent's local-to-world matrix (and its inverse), identity for objects without a parent
+    parent_matrix: Matrix4,
+    inverse_parent_matrix: Matrix4,
+    world_matrix: Matrix4,
+    inverse_world_matrix: Matrix4,
 
     rotation: Quaternion,
     scale: Vector3<FloatType>,
@@ -25,6 +35,12 @@ impl Transform {
         let mut new_self = Self {
             matrix: Matrix4::identity_matrix(),
             inverse_matrix: Matrix4::identity_matrix(),
+            normal_matrix: Matrix3::identity_matrix(),
+
+            parent_matrix: Matrix4::identity_matrix(),
+            inverse_parent_matrix: Matrix4::identity_matrix(),
+            world_matrix: Matrix4::identity_matrix(),
+            inverse_world_matrix: Matrix4::identity_matrix(),
 
             rotation,
             scale,
@@ -37,6 +53,13 @@ impl Transform {
         new_self
     }
 
+    // The matrix maps local space into the parent's space, see `Matrix4::decompose` for what
+    // it can't contain
+    pub fn from_matrix(matrix: &Matrix4) -> Result<Self, MathError> {
+        let decomposed = matrix.decompose()?;
+        Ok(Self::new(decomposed.translation, decomposed.rotation, decomposed.scale))
+    }
+
     pub fn update(&mut self) {
         if !self.is_dirty {
             return;
@@ -50,9 +73,16 @@ impl Transform {
         // Update rotation and scale
         self.rotation.edit_3d_matrix(&mut self.matrix, self.scale);
 
-        // Generate inverse matrix from original matrix
-        // Should NEVER fail
-        self.inverse_matrix = self.matrix.invert(true).unwrap();
+        self.inverse_matrix = inverse_trs_matrix(self.position, self.rotation, self.scale);
+
+        self.world_matrix = &self.parent_matrix * &self.matrix;
+        self.inverse_world_matrix = &self.inverse_matrix * &self.inverse_parent_matrix;
+
+        for row in 0..3 {
+            for col in 0..3 {
+                self.normal_matrix[row][col] = self.inverse_world_matrix[col][row];
+            }
+        }
 
         // Set dirty flag to false, regardless of initial value
         self.is_dirty = false;
@@ -67,22 +97,152 @@ impl Transform {
     // The original matrix (local_to_world) directly move/orient the point.
     // Its inverse move/orient the frame of reference.
     pub fn local_to_world(&self, pos: Vector3<FloatType>) -> Vector3<FloatType> {
-        transform_3d_point(&self.matrix, pos)
+        transform_3d_point(&self.world_matrix, pos)
     }
 
     pub fn world_to_local(&self, pos: Vector3<FloatType>) -> Vector3<FloatType> {
-        transform_3d_point(&self.inverse_matrix, pos)
+        transform_3d_point(&self.inverse_world_matrix, pos)
     }
 
-    pub fn rotate(&mut self, q: Quaternion) {
-        self.rotation *= q;
+    pub fn local_to_world_matrix(&self) -> &Matrix4 {
+        &self.world_matrix
+    }
+
+    pub fn world_to_local_matrix(&self) -> &Matrix4 {
+        &self.inverse_world_matrix
+    }
+
+    pub fn normal_matrix(&self) -> &Matrix3 {
+        &self.normal_matrix
+    }
+
+    // Directions (such as tangents or the edges of a face) are affected by rotation and scale,
+    // but not by translation
+    pub fn transform_direction(&self, direction: Vector3<FloatType>) -> Vector3<FloatType> {
+        transform_3d_direction(&self.world_matrix, direction)
+    }
+
+    // Normals have to stay perpendicular to their surface, which the world matrix does not
+    // guarantee with non-uniform scale, so they go through the normal matrix instead.
+    // The result has unit length.
+    pub fn transform_normal(&self, normal: Vector3<FloatType>) -> Vector3<FloatType> {
+        self.normal_matrix.multiply_vector(normal).normalized()
+    }
+
+    // Maps local space into the parent's space
+    pub fn local_to_parent_matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn parent_to_local_matrix(&self) -> &Matrix4 {
+        &self.inverse_matrix
+    }
+
+    // Called by the parent whenever its world matrix changes. Only takes effect on `update`.
+    pub fn set_parent_matrix(&mut self, matrix: &Matrix4, inverse_matrix: &Matrix4) {
+        self.parent_matrix = matrix.clone();
+        self.inverse_parent_matrix = inverse_matrix.clone();
         self.is_dirty = true;
     }
 
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    pub fn position(&self) -> Vector3<FloatType> {
+        self.position
+    }
+
     pub fn set_position(&mut self, p: Vector3<FloatType>) {
         self.position = p;
         self.is_dirty = true;
     }
+
+    pub fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, q: Quaternion) {
+        self.rotation = q;
+        self.is_dirty = true;
+    }
+
+    pub fn scale(&self) -> Vector3<FloatType> {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, s: Vector3<FloatType>) {
+        self.scale = s;
+        self.is_dirty = true;
+    }
+
+    // Moves along the object's own axes. Scale does not affect the distance moved.
+    pub fn translate(&mut self, delta: Vector3<FloatType>) {
+        let delta = self.rotation.rotate_vector(delta);
+        self.set_position(self.position + delta);
+    }
+
+    pub fn translate_world(&mut self, delta: Vector3<FloatType>) {
+        let delta = transform_3d_direction(&self.inverse_parent_matrix, delta);
+        self.set_position(self.position + delta);
+    }
+
+    // Rotates around the object's own axes
+    pub fn rotate(&mut self, q: Quaternion) {
+        self.rotation *= q;
+        self.is_dirty = true;
+    }
+
+    // Rotates around the world axes, while staying in place
+    pub fn rotate_world(&mut self, q: Quaternion) {
+        // Bring the rotation into the parent's space first: parent^-1 * q * parent
+        let parent_rotation = self.parent_rotation();
+        self.rotation = parent_rotation.conjugate() * q * parent_rotation * self.rotation;
+        self.is_dirty = true;
+    }
+
+    // Rotation part of the parent's world matrix, assuming it has no shear or negative scale
+    fn parent_rotation(&self) -> Quaternion {
+        let mut rotation = Matrix3::identity_matrix();
+        for col in 0..3 {
+            let axis = Vector3::new([
+                self.parent_matrix[0][col], self.parent_matrix[1][col], self.parent_matrix[2][col]
+            ]).normalized();
+
+            for row in 0..3 {
+                rotation[row][col] = axis[row];
+            }
+        }
+
+        Quaternion::from_rotation_matrix(&rotation)
+    }
+}
+
+// Inverse of translation * rotation * scale, which is (1/scale) * rotation^T * -translation.
+// Much cheaper than a general matrix inversion. The rotation matrix built from a quaternion is
+// only orthonormal up to rounding, so each of its axes is divided by its squared length: that
+// keeps the result the inverse of the matrix actually used, instead of the ideal one.
+// A scale of 0 has no inverse, and gives infinite values.
+fn inverse_trs_matrix(
+    position: Vector3<FloatType>, rotation: Quaternion, scale: Vector3<FloatType>
+) -> Matrix4 {
+    let rotation = rotation.to_rotation_matrix();
+    let mut inverse = Matrix4::identity_matrix();
+
+    for row in 0..3 {
+        let axis = rotation.col(row);
+        let sq_length = axis.sq_length();
+        for col in 0..3 {
+            inverse[row][col] = axis[col] / (sq_length * scale[row]);
+        }
+    }
+
+    let inverse_position = transform_3d_direction(&inverse, position);
+    for row in 0..3 {
+        inverse[row][3] = -inverse_position[row];
+    }
+
+    inverse
 }
 
 impl Default for Transform {
@@ -102,10 +262,11 @@ mod tests {
     use vector::tests::approx_cmp_vector;
 
     use crate::math_utils::quaternion::tests::approx_cmp_quaternion;
+    use crate::math_utils::consts::{FRAC_PI_2, FRAC_PI_4, PI};
 
     fn init_test_transform() -> Transform {
-        let deg45 = std::f32::consts::FRAC_PI_4;
-        let deg20 = std::f32::consts::PI / 9.0;
+        let deg45 = FRAC_PI_4;
+        let deg20 = PI / 9.0;
         Transform::new(
             vector![1.0, 7.0, 2.5],
             Quaternion::from_euler_angles(deg45, 0.0, deg20),
@@ -119,11 +280,11 @@ mod tests {
         let pos = vector![2.5, 1.89, 10.7];
 
         let pos_in_world = transform.local_to_world(pos);
-        approx_cmp_vector(pos_in_world, vector![
+        assert!(pos_in_world.approx_eq(vector![
             547988391453.0/100000000000.0,
             100056555803.0/50000000000.0,
             1140247437279.0/100000000000.0
-       ]);
+        ], (1e-5, 4)));
     }
 
     #[test]
@@ -132,11 +293,11 @@ mod tests {
         let pos = vector![1.0, 2.0, -0.5];
 
         let pos_in_local = transform.world_to_local(pos);
-        approx_cmp_vector(pos_in_local, vector![
+        assert!(pos_in_local.approx_eq(vector![
             -1209223815000000000.0/707106781005207013.0,
             -2721817732419718022000000000.0/499999999739863875201055153.0,
             600497389404096983000000000.0/499999999739863875201055153.0
-        ]);
+        ], (1e-5, 4)));
     }
 
     #[test]
@@ -144,7 +305,7 @@ mod tests {
         let mut transform = init_test_transform();
         let initial_rotation = transform.rotation;
 
-        let deg45 = std::f32::consts::FRAC_PI_4;
+        let deg45 = FRAC_PI_4;
         let rot = Quaternion::from_euler_angles(deg45, 0.0, deg45);
 
         transform.rotate(rot);
@@ -164,4 +325,143 @@ mod tests {
         transform.matrix.approx_eq(Matrix4::identity_matrix(), (1.0, 1));
         transform.inverse_matrix.approx_eq(Matrix4::identity_matrix(), (1.0, 1));
     }
+
+    #[test]
+    fn test_non_uniform_scale_with_rotation() {
+        let deg90 = FRAC_PI_2;
+        let mut transform = Transform::new(
+            vector![1.0, 0.0, 0.0],
+            Quaternion::from_euler_angles(0.0, 0.0, deg90),
+            vector![2.0, 3.0, 1.0]
+        );
+
+        // Scaled along local x first, then rotated onto y
+        let margin = (1e-5, 4);
+        assert!(transform.local_to_world(vector![1.0, 0.0, 0.0]).approx_eq(vector![1.0, 2.0, 0.0], margin));
+        assert!(transform.local_to_world(vector![0.0, 1.0, 0.0]).approx_eq(vector![-2.0, 0.0, 0.0], margin));
+        assert!(transform.world_to_local(vector![1.0, 2.0, 0.0]).approx_eq(vector![1.0, 0.0, 0.0], margin));
+
+        transform.set_scale(vector![1.0, 1.0, 1.0]);
+        assert!(transform.is_dirty);
+        transform.update();
+        assert!(transform.local_to_world(vector![1.0, 0.0, 0.0]).approx_eq(vector![1.0, 1.0, 0.0], margin));
+    }
+
+    #[test]
+    fn test_translate_local_and_world() {
+        let deg90 = FRAC_PI_2;
+        let mut transform = Transform::new(
+            vector![0.0, 0.0, 0.0],
+            Quaternion::from_euler_angles(0.0, 0.0, deg90),
+            vector![5.0, 5.0, 5.0]
+        );
+
+        // Local x points along world y, and scale is ignored
+        transform.translate(vector![1.0, 0.0, 0.0]);
+        approx_cmp_vector(transform.position(), vector![0.0, 1.0, 0.0]);
+
+        transform.translate_world(vector![1.0, 0.0, 0.0]);
+        approx_cmp_vector(transform.position(), vector![1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rotate_local_and_world() {
+        let deg90 = FRAC_PI_2;
+        let around_x = Quaternion::from_euler_angles(deg90, 0.0, 0.0);
+        let around_z = Quaternion::from_euler_angles(0.0, 0.0, deg90);
+
+        let mut local = Transform::default();
+        local.set_rotation(around_z);
+        local.rotate(around_x);
+        local.update();
+
+        let mut world = Transform::default();
+        world.set_rotation(around_z);
+        world.rotate_world(around_x);
+        world.update();
+
+        // Local: x stays on its rotated axis (world y). World: z's turn is followed by a turn
+        // around world x, which carries y onto z.
+        approx_cmp_vector(local.local_to_world(vector![1.0, 0.0, 0.0]), vector![0.0, 1.0, 0.0]);
+        approx_cmp_vector(world.local_to_world(vector![1.0, 0.0, 0.0]), vector![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_translate_world_with_parent() {
+        let mut transform = Transform::default();
+        let mut parent = Transform::default();
+        parent.set_scale(vector![2.0, 2.0, 2.0]);
+        parent.update();
+        transform.set_parent_matrix(parent.local_to_world_matrix(), parent.world_to_local_matrix());
+
+        // Parent space is twice as big, so half the distance in it
+        transform.translate_world(vector![4.0, 0.0, 0.0]);
+        transform.update();
+        approx_cmp_vector(transform.position(), vector![2.0, 0.0, 0.0]);
+        approx_cmp_vector(transform.local_to_world(vector![0.0, 0.0, 0.0]), vector![4.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_transform_from_matrix() {
+        let original = init_test_transform();
+        let transform = Transform::from_matrix(original.local_to_parent_matrix()).unwrap();
+
+        approx_cmp_vector(transform.position(), original.position());
+        approx_cmp_vector(transform.scale(), original.scale());
+        assert!(transform.rotation().approx_eq(original.rotation(), (1e-6, 4)));
+
+        let mut sheared = Matrix4::identity_matrix();
+        sheared[1][0] = 1.0;
+        assert!(matches!(Transform::from_matrix(&sheared), Err(MathError::Shear)));
+    }
+
+    #[test]
+    fn test_transform_normal_with_non_uniform_scale() {
+        let transform = Transform::new(
+            vector![5.0, 0.0, 0.0],
+            Quaternion::default(),
+            vector![2.0, 1.0, 1.0]
+        );
+
+        // A slope going up along x flattens out when stretched along x
+        let tangent = vector![1.0, 1.0, 0.0];
+        let normal = vector![-1.0, 1.0, 0.0];
+        let world_tangent = transform.transform_direction(tangent);
+        let world_normal = transform.transform_normal(normal);
+
+        approx_cmp_vector(world_tangent, vector![2.0, 1.0, 0.0]);
+        assert!(world_tangent.dot(world_normal).abs() < 1e-6);
+        assert!((world_normal.length() - 1.0).abs() < 1e-6);
+
+        // Transforming the normal like a point or direction breaks perpendicularity
+        assert!(world_tangent.dot(transform.transform_direction(normal)).abs() > 1.0);
+    }
+
+    #[test]
+    fn test_inverse_matches_general_inversion() {
+        let transform = Transform::new(
+            vector![1.0, -2.0, 3.0],
+            Quaternion::from_euler_angles(0.3, -0.5, 1.2),
+            vector![2.0, 0.5, 3.0]
+        );
+        let general_inverse = transform.local_to_parent_matrix().invert().unwrap();
+
+        assert!(transform.parent_to_local_matrix().clone().approx_eq(general_inverse, (1e-5, 4)));
+    }
+
+    #[test]
+    fn test_inverse_of_quarter_turn() {
+        // Nearly-zero pivots here used to throw the general inversion off
+        let transform = Transform::new(
+            vector![4.0, 0.0, -1.0],
+            Quaternion::from_euler_angles(0.0, FRAC_PI_2, 0.0),
+            vector![1.0, 2.0, 1.0]
+        );
+        let point = vector![0.5, -3.0, 7.0];
+
+        let round_trip = transform.world_to_local(transform.local_to_world(point));
+        assert!(round_trip.approx_eq(point, (1e-5, 4)));
+        assert!((transform.local_to_parent_matrix() * transform.parent_to_local_matrix())
+            .approx_eq(Matrix4::identity_matrix(), (1e-6, 4)));
+    }
 }
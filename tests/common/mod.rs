@@ -0,0 +1,28 @@
+use zesty_engine2::math_utils::FloatType;
+use zesty_engine2::math_utils::matrix::Matrix;
+use zesty_engine2::math_utils::vector::Vector;
+
+// Relative to the size of the values being compared, with a floor for values near 0
+pub const TOLERANCE: FloatType = 1e-4;
+
+// Whether `result`, computed as a * b * v, is v again. Each entry sums up terms that can be much
+// larger than the result, e.g. big translations that cancel out, so the allowed error grows with
+// the size of those terms.
+pub fn is_round_trip<const N: usize>(
+    a: &Matrix<N>, b: &Matrix<N>, v: Vector<N, FloatType>, result: Vector<N, FloatType>
+) -> bool {
+    (0..N).all(|row| {
+        let magnitude: FloatType = (0..N)
+            .map(|k| (0..N).map(|j| (a[row][k] * b[k][j] * v[j]).abs()).sum::<FloatType>())
+            .sum();
+        (result[row] - v[row]).abs() <= TOLERANCE * magnitude.max(1.0)
+    })
+}
+
+// Whether a * b is the identity, i.e. whether it brings back every basis vector
+pub fn is_identity_product<const N: usize>(a: &Matrix<N>, b: &Matrix<N>) -> bool {
+    let product = a * b;
+    let identity = Matrix::<N>::identity_matrix();
+
+    (0..N).all(|col| is_round_trip(a, b, identity.col(col), product.col(col)))
+}
@@ -0,0 +1,140 @@
+use proptest::prelude::*;
+
+use zesty_engine2::math_utils::FloatType;
+use zesty_engine2::math_utils::consts::PI;
+use zesty_engine2::math_utils::matrix::{Matrix, Matrix3};
+use zesty_engine2::math_utils::quaternion::Quaternion;
+use zesty_engine2::math_utils::vector::Vector3;
+use zesty_engine2::transform::Transform;
+
+mod common;
+use common::{TOLERANCE, is_identity_product, is_round_trip};
+
+// Random inputs are kept within these ranges. Transforms go well beyond the points, since a
+// transform is perfectly invertible whatever units its translation and scale are in.
+// proptest shrinks any failing input down to a minimal counterexample before reporting it.
+const COORDINATE_RANGE: FloatType = 100.0;
+const TRANSLATION_RANGE: FloatType = 1e6;
+// Scales go from 10^-3 to 10^3
+const SCALE_EXPONENT_RANGE: FloatType = 3.0;
+
+fn point() -> impl Strategy<Value = Vector3<FloatType>> {
+    prop::array::uniform3(-COORDINATE_RANGE..COORDINATE_RANGE).prop_map(Vector3::new)
+}
+
+fn translation() -> impl Strategy<Value = Vector3<FloatType>> {
+    prop::array::uniform3(-TRANSLATION_RANGE..TRANSLATION_RANGE).prop_map(Vector3::new)
+}
+
+fn scale() -> impl Strategy<Value = Vector3<FloatType>> {
+    prop::array::uniform3(-SCALE_EXPONENT_RANGE..SCALE_EXPONENT_RANGE)
+        .prop_map(|exponents| Vector3::new(exponents.map(|e| (10.0 as FloatType).powf(e))))
+}
+
+// Uniform enough over all rotations for these tests. Near-zero axes are left out, since they
+// have no direction to normalize.
+fn rotation() -> impl Strategy<Value = Quaternion> {
+    (prop::array::uniform3(-1.0..1.0 as FloatType), -PI..PI)
+        .prop_filter("axis must not be zero", |(axis, _)| Vector3::new(*axis).length() > 0.01)
+        .prop_map(|(axis, angle)| Quaternion::from_axis_angle(Vector3::new(axis), angle))
+}
+
+fn transform() -> impl Strategy<Value = Transform> {
+    (translation(), rotation(), scale())
+        .prop_map(|(position, rotation, scale)| Transform::new(position, rotation, scale))
+}
+
+fn is_close(a: FloatType, b: FloatType) -> bool {
+    (a - b).abs() <= TOLERANCE * a.abs().max(b.abs()).max(1.0)
+}
+
+fn is_close_vector(a: Vector3<FloatType>, b: Vector3<FloatType>) -> bool {
+    let scale = a.length().max(b.length()).max(1.0);
+    (a - b).length() <= TOLERANCE * scale
+}
+
+fn is_close_matrix<const N: usize>(a: &Matrix<N>, b: &Matrix<N>) -> bool {
+    (0..N).all(|row| (0..N).all(|col| is_close(a[row][col], b[row][col])))
+}
+
+proptest! {
+    #[test]
+    fn rotation_matrix_rotates_like_quaternion(q in rotation(), v in point()) {
+        let by_matrix = q.to_rotation_matrix().multiply_vector(v);
+        let by_quaternion = q.rotate_vector(v);
+
+        prop_assert!(is_close_vector(by_matrix, by_quaternion), "{:?} != {:?}", by_matrix, by_quaternion);
+    }
+
+    #[test]
+    fn rotation_matrix_is_orthonormal(q in rotation()) {
+        let matrix = q.to_rotation_matrix();
+
+        prop_assert!(is_close_matrix(&(&matrix * &matrix.transpose()), &Matrix3::identity_matrix()));
+        prop_assert!(is_close(matrix.determinant(), 1.0));
+    }
+
+    #[test]
+    fn matrix_to_quaternion_round_trip(q in rotation()) {
+        let result = Quaternion::from_rotation_matrix(&q.to_rotation_matrix());
+
+        // q and -q are the same rotation
+        prop_assert!(is_close(result.dot(q).abs(), 1.0), "{:?} != {:?}", result, q);
+    }
+
+    #[test]
+    fn quaternion_product_matches_matrix_product(q1 in rotation(), q2 in rotation()) {
+        let by_quaternion = (q1 * q2).to_rotation_matrix();
+        let by_matrix = &q1.to_rotation_matrix() * &q2.to_rotation_matrix();
+
+        prop_assert!(is_close_matrix(&by_quaternion, &by_matrix));
+    }
+
+    #[test]
+    fn rotation_preserves_norm(q in rotation(), v in point()) {
+        prop_assert!(is_close(q.rotate_vector(v).length(), v.length()));
+        prop_assert!(is_close(q.to_rotation_matrix().multiply_vector(v).length(), v.length()));
+    }
+
+    #[test]
+    fn world_to_local_undoes_local_to_world(transform in transform(), p in point()) {
+        let result = transform.world_to_local(transform.local_to_world(p));
+
+        prop_assert!(is_round_trip(
+            transform.world_to_local_matrix(), transform.local_to_world_matrix(),
+            p.extend(1.0), result.extend(1.0)
+        ), "{:?} != {:?}", result, p);
+    }
+
+    #[test]
+    fn world_to_local_undoes_local_to_world_with_parent(
+        parent in transform(), mut child in transform(), p in point()
+    ) {
+        child.set_parent_matrix(parent.local_to_world_matrix(), parent.world_to_local_matrix());
+        child.update();
+        let result = child.world_to_local(child.local_to_world(p));
+
+        prop_assert!(is_round_trip(
+            child.world_to_local_matrix(), child.local_to_world_matrix(),
+            p.extend(1.0), result.extend(1.0)
+        ), "{:?} != {:?}", result, p);
+    }
+
+    #[test]
+    fn transform_matrices_are_inverses(transform in transform()) {
+        let matrix = transform.local_to_world_matrix();
+        let inverse = transform.world_to_local_matrix();
+
+        prop_assert!(is_identity_product(matrix, inverse), "{:?}", matrix * inverse);
+        prop_assert!(is_identity_product(inverse, matrix), "{:?}", inverse * matrix);
+    }
+
+    #[test]
+    fn inverted_transform_matrix_round_trips(transform in transform(), p in point()) {
+        let matrix = transform.local_to_world_matrix();
+        let inverse = matrix.invert().unwrap();
+        let result = inverse.multiply_vector(transform.local_to_world(p).extend(1.0));
+
+        prop_assert!(is_round_trip(&inverse, matrix, p.extend(1.0), result), "{:?} != {:?}", result, p);
+    }
+}
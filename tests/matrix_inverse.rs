@@ -0,0 +1,40 @@
+use proptest::prelude::*;
+
+use zesty_engine2::math_utils::matrix::{Matrix, Matrix3};
+use zesty_engine2::math_utils::FloatType;
+
+mod common;
+use common::is_identity_product;
+
+// Entries in [-1, 1], with the diagonal pushed away from 0 so that every row is dominated by its
+// diagonal entry. Such matrices are always invertible, and never badly conditioned.
+fn diagonally_dominant_matrix<const N: usize>() -> impl Strategy<Value = Matrix<N>> {
+    (
+        prop::array::uniform::<_, N>(prop::array::uniform::<_, N>(-1.0..=1.0 as FloatType)),
+        prop::array::uniform::<_, N>(prop::bool::ANY)
+    ).prop_map(|(mut rows, is_negative)| {
+        for (i, row) in rows.iter_mut().enumerate() {
+            let magnitude = N as FloatType + row[i].abs();
+            row[i] = if is_negative[i] { -magnitude } else { magnitude };
+        }
+        Matrix::new(rows)
+    })
+}
+
+proptest! {
+    #[test]
+    fn inverse_of_3x3_gives_identity(matrix in diagonally_dominant_matrix::<3>()) {
+        let inverse: Matrix3 = matrix.invert().unwrap();
+
+        prop_assert!(is_identity_product(&matrix, &inverse), "{:?}", &matrix * &inverse);
+        prop_assert!(is_identity_product(&inverse, &matrix), "{:?}", &inverse * &matrix);
+    }
+
+    #[test]
+    fn inverse_of_4x4_gives_identity(matrix in diagonally_dominant_matrix::<4>()) {
+        let inverse = matrix.invert().unwrap();
+
+        prop_assert!(is_identity_product(&matrix, &inverse), "{:?}", &matrix * &inverse);
+        prop_assert!(is_identity_product(&inverse, &matrix), "{:?}", &inverse * &matrix);
+    }
+}
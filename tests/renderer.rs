@@ -1,8 +1,112 @@
 use zesty_engine2::renderer;
 use zesty_engine2::camera;
 
+use zesty_engine2::math_utils::vector::prelude::*;
+use zesty_engine2::math_utils::FloatType;
+use zesty_engine2::object::{Mesh, Object};
+use renderer::{Color, RenderType, Renderer};
+use camera::Camera;
+
+const BUFFER_WIDTH: usize = 64;
+const BUFFER_HEIGHT: usize = 36;
+// Where a point straight in front of the camera lands
+const CENTER: usize = BUFFER_WIDTH / 2 + (BUFFER_HEIGHT / 2) * BUFFER_WIDTH;
+
+// Renderer and cleared buffer of the size above, with a camera at the origin looking down +z
+fn init_renderer() -> (Renderer, Vec<u32>, Camera) {
+    let mut renderer = Renderer::new();
+    renderer.update_buffer_size(BUFFER_WIDTH, BUFFER_HEIGHT);
+    let buffer = vec![0_u32; BUFFER_WIDTH * BUFFER_HEIGHT];
+
+    (renderer, buffer, Camera::new(1.0, 100.0, 60.0))
+}
+
 
 #[test]
 fn test_render_vertices() {
+    let (mut renderer, mut buffer, camera) = init_renderer();
+
+    let mut mesh = Mesh::new();
+    mesh.vertices.push(vector![0.0, 0.0, 0.0]);
+    let mut obj = Object::from_mesh(mesh);
+    obj.transform.set_position(vector![0.0, 0.0, 10.0]);
+    obj.transform.update();
+
+    renderer.render(&obj, &camera, &mut buffer, RenderType::Vertex).unwrap();
+
+    // A point straight in front of the camera lands in the middle of the buffer
+    assert_eq!(buffer[CENTER], Color::WHITE.u32_color());
+    assert_eq!(buffer[0], 0);
+}
+
+#[test]
+fn test_render_faces_with_reversed_z() {
+    let (mut renderer, mut buffer, mut camera) = init_renderer();
+
+    // Two quads facing the camera, the far one covers the whole view
+    let mut mesh = Mesh::new();
+    mesh.vertices = vec![
+        vector![-1.0, -1.0, 5.0], vector![1.0, -1.0, 5.0], vector![1.0, 1.0, 5.0], vector![-1.0, 1.0, 5.0],
+        vector![-30.0, -30.0, 20.0], vector![30.0, -30.0, 20.0], vector![30.0, 30.0, 20.0], vector![-30.0, 30.0, 20.0]
+    ];
+    mesh.faces = vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]];
+    let obj = Object::from_mesh(mesh);
+
+    camera.set_reversed_z(true);
+    renderer.render(&obj, &camera, &mut buffer, RenderType::Face).unwrap();
+
+    assert_ne!(buffer[CENTER], 0);
+    assert_ne!(buffer[0], 0);
+
+    // Depth of the near quad won, with reversed-Z near is bigger
+    let near_depth = camera.project_to_ncd_space(vector![0.0, 0.0, 5.0]).z();
+    assert!((renderer.depth_buffer()[CENTER] - near_depth).abs() < 1e-5);
+}
+
+#[test]
+fn test_cull_object_behind_camera() {
+    let (mut renderer, mut buffer, camera) = init_renderer();
+
+    let mut mesh = Mesh::new();
+    mesh.vertices = vec![vector![-1.0, -1.0, 0.0], vector![1.0, -1.0, 0.0], vector![0.0, 1.0, 0.0]];
+    mesh.faces = vec![[0, 1, 2]];
+    let mut obj = Object::from_mesh(mesh);
+    obj.transform.set_position(vector![0.0, 0.0, -10.0]);
+    obj.transform.update();
+
+    for render_type in [RenderType::Vertex, RenderType::Edge, RenderType::Face] {
+        renderer.render(&obj, &camera, &mut buffer, render_type).unwrap();
+    }
+
+    assert!(buffer.iter().all(|pixel| *pixel == 0));
+}
+
+#[test]
+fn test_render_children_share_depth_buffer() {
+    let (mut renderer, mut buffer, camera) = init_renderer();
+
+    let quad = |half_size: FloatType| {
+        let mut mesh = Mesh::new();
+        mesh.vertices = vec![
+            vector![-half_size, -half_size, 0.0], vector![half_size, -half_size, 0.0],
+            vector![half_size, half_size, 0.0], vector![-half_size, half_size, 0.0]
+        ];
+        mesh.faces = vec![[0, 1, 2], [0, 2, 3]];
+        mesh
+    };
+
+    // The child is drawn after its parent, but sits behind it
+    let mut parent = Object::from_mesh(quad(1.0));
+    parent.transform.set_position(vector![0.0, 0.0, 5.0]);
+    let mut child = Object::from_mesh(quad(30.0));
+    child.transform.set_position(vector![0.0, 0.0, 15.0]);
+    parent.add_child(child);
+    parent.update_transforms();
+
+    renderer.render(&parent, &camera, &mut buffer, RenderType::Face).unwrap();
 
+    let near_depth = camera.project_to_ncd_space(vector![0.0, 0.0, 5.0]).z();
+    assert!((renderer.depth_buffer()[CENTER] - near_depth).abs() < 1e-5);
+    // The child still shows around the parent
+    assert_ne!(buffer[0], 0);
 }
@@ -1,15 +1,17 @@
-use std::f32::consts::FRAC_PI_4;
+// Expected values are written out as found in external references, not as the std constants
+#![allow(clippy::approx_constant, clippy::excessive_precision)]
 
 use float_cmp::ApproxEq;
 
 use zesty_engine2::math_utils;
 use math_utils::{transform_3d_point, vector, matrix, quaternion, FloatType};
+use math_utils::consts::FRAC_PI_4;
 use quaternion::Quaternion;
 use vector::prelude::*;
 use matrix::Matrix4;
 
 
-const DEG_45: f32 = FRAC_PI_4;
+const DEG_45: FloatType = FRAC_PI_4;
 
 #[test]
 fn test_translate_point() {